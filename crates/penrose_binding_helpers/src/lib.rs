@@ -0,0 +1,416 @@
+//! Key binding parsing helpers shared by penrose and the compile time validation in penrose_proc
+//!
+//! Keeping these in one place means that the bindings accepted by `gen_keybindings` at compile
+//! time are parsed in exactly the same way as they are at runtime.
+#![warn(missing_docs)]
+
+use std::{error::Error, fmt};
+
+/// The prefix for key bindings that are run when a modifier key is tapped on its own: 'tap:M'
+pub const TAP_PREFIX: &str = "tap:";
+
+/// The prefix for giving a raw X key code in place of a key name in a binding: 'M-code:133'
+pub const KEY_CODE_PREFIX: &str = "code:";
+
+/// The prefix for disabling a key binding without removing it: '#M-j'
+pub const DISABLED_PREFIX: char = '#';
+
+/// Friendlier names that can be used in key bindings in place of the X keysym names they map to.
+///
+/// Aliases are matched ignoring case. None of the aliases are themselves valid keysym names so
+/// they never shadow a real key.
+pub const KEY_ALIASES: [(&str, &str); 15] = [
+    ("Space", "space"),
+    ("Enter", "Return"),
+    ("Esc", "Escape"),
+    ("KPEnter", "KP_Enter"),
+    ("KPAdd", "KP_Add"),
+    ("KPSubtract", "KP_Subtract"),
+    ("KPMultiply", "KP_Multiply"),
+    ("KPDivide", "KP_Divide"),
+    ("BrightnessUp", "XF86MonBrightnessUp"),
+    ("BrightnessDown", "XF86MonBrightnessDown"),
+    ("VolumeUp", "XF86AudioRaiseVolume"),
+    ("VolumeDown", "XF86AudioLowerVolume"),
+    ("Mute", "XF86AudioMute"),
+    ("AudioPlay", "XF86AudioPlay"),
+    ("AudioNext", "XF86AudioNext"),
+];
+
+/// Expand a key name using [KEY_ALIASES], leaving anything that is not an alias unchanged
+///
+/// ```
+/// # use penrose_binding_helpers::resolve_key_alias;
+/// assert_eq!(resolve_key_alias("esc"), "Escape");
+/// assert_eq!(resolve_key_alias("Return"), "Return");
+/// ```
+pub fn resolve_key_alias(name: &str) -> &str {
+    KEY_ALIASES
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+        .map(|&(_, keysym)| keysym)
+        .unwrap_or(name)
+}
+
+/// Find the known key name that a key name in a binding refers to.
+///
+/// Exact matches win, followed by [KEY_ALIASES] and then a match ignoring case (as long as
+/// exactly one known name matches).
+///
+/// ```
+/// # use penrose_binding_helpers::find_key_name;
+/// let known = ["Return", "space", "ae", "AE"];
+///
+/// assert_eq!(find_key_name("Return", known.iter().copied()), Some("Return"));
+/// assert_eq!(find_key_name("Enter", known.iter().copied()), Some("Return"));
+/// assert_eq!(find_key_name("SPACE", known.iter().copied()), Some("space"));
+/// assert_eq!(find_key_name("Ae", known.iter().copied()), None);
+/// ```
+pub fn find_key_name<'a, I>(name: &str, known: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+    I::IntoIter: Clone,
+{
+    let known = known.into_iter();
+    let alias = resolve_key_alias(name);
+    if let Some(k) = known
+        .clone()
+        .find(|&k| k == name)
+        .or_else(|| known.clone().find(|&k| k == alias))
+    {
+        return Some(k);
+    }
+
+    let mut matches = known.filter(|k| k.eq_ignore_ascii_case(name));
+    match (matches.next(), matches.next()) {
+        (Some(k), None) => Some(k),
+        _ => None,
+    }
+}
+
+/// Parse the number following [KEY_CODE_PREFIX] in a raw key code binding.
+///
+/// Codes may be given in decimal, hex or octal: '133', '0x85' and '0o205' all give `Some(133)`.
+/// Anything that is not a number in the range of X key codes (0..=255) gives `None`.
+pub fn parse_key_code(s: &str) -> Option<u8> {
+    let s = s.trim();
+    let (digits, radix) = match s.get(..2) {
+        Some("0x") | Some("0X") => (&s[2..], 16),
+        Some("0o") | Some("0O") => (&s[2..], 8),
+        _ => (s, 10),
+    };
+
+    if digits.starts_with('+') {
+        return None;
+    }
+
+    u8::from_str_radix(digits, radix).ok()
+}
+
+/// Whether a key name is blank and so should be skipped: empty key names are used as spacers
+/// when laying out long lists of bindings.
+pub fn is_spacer(s: &str) -> bool {
+    s.trim().is_empty()
+}
+
+/// The ways in which expanding a key binding template using [expand_template] can fail
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    /// The number of `{}` placeholders in the template does not match the number of lists of
+    /// key names: (template, expected, found)
+    InvalidPlaceholders(String, usize, usize),
+
+    /// There were no key names to substitute into the template
+    NoKeyNames(String),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidPlaceholders(template, expected, found) => write!(
+                f,
+                "'{}' is an invalid template: expected {} '{{}}' placeholder{} (one for each \
+                list of key names) but found {}",
+                template,
+                expected,
+                if *expected == 1 { "" } else { "s" },
+                found
+            ),
+            Self::NoKeyNames(template) => write!(
+                f,
+                "template '{}' does not produce any bindings: the list of key names is empty",
+                template
+            ),
+        }
+    }
+}
+
+impl Error for TemplateError {}
+
+/// Expand a key binding template into one binding for each combination of the given key names.
+///
+/// The template must contain one `{}` placeholder for each list of key names (placeholders can
+/// appear anywhere in the template) and each placeholder is replaced by a name from the
+/// corresponding list, with every combination of names being generated. Blank key names are
+/// skipped (see [is_spacer]) so they can be used as spacers.
+///
+/// ```
+/// # use penrose_binding_helpers::expand_template;
+/// assert_eq!(
+///     expand_template("M-S-{}", &[&["1", "2", "3"]]).unwrap(),
+///     vec!["M-S-1", "M-S-2", "M-S-3"]
+/// );
+/// assert_eq!(
+///     expand_template("M-{}-{}", &[&["S", "C"], &["j", "k"]]).unwrap(),
+///     vec!["M-S-j", "M-S-k", "M-C-j", "M-C-k"]
+/// );
+/// assert!(expand_template("M-S-1", &[&["1", "2", "3"]]).is_err());
+/// ```
+pub fn expand_template(template: &str, keys: &[&[&str]]) -> Result<Vec<String>, TemplateError> {
+    let placeholders = template.matches("{}").count();
+    if placeholders != keys.len() {
+        return Err(TemplateError::InvalidPlaceholders(
+            template.into(),
+            keys.len(),
+            placeholders,
+        ));
+    }
+
+    let mut expanded = vec![template.to_string()];
+    for names in keys.iter() {
+        expanded = expanded
+            .iter()
+            .flat_map(|partial| {
+                names
+                    .iter()
+                    .filter(|k| !is_spacer(k))
+                    .map(move |k| partial.replacen("{}", k, 1))
+            })
+            .collect();
+    }
+
+    if expanded.is_empty() {
+        return Err(TemplateError::NoKeyNames(template.into()));
+    }
+
+    Ok(expanded)
+}
+
+/// Expand the modifier groups (and key name group) in a key binding into the individual bindings
+/// they represent.
+///
+/// A modifier group is a comma separated list of alternatives written in square brackets in
+/// place of a single modifier: `M-[S,C]-j` is expanded to `M-S-j` and `M-C-j`. An empty
+/// alternative leaves that modifier out, so `M-[,S]-j` gives `M-j` and `M-S-j`. In the same way,
+/// a `|` separated list of key names in parentheses binds each of those keys: `M-(Return|KP_Enter)`
+/// is expanded to `M-Return` and `M-KP_Enter`. Multiple groups expand to every combination of
+/// their alternatives. Bindings without any groups are returned unchanged.
+///
+/// ```
+/// # use penrose_binding_helpers::expand_modifier_groups;
+/// assert_eq!(expand_modifier_groups("M-[S,C]-j"), vec!["M-S-j", "M-C-j"]);
+/// assert_eq!(expand_modifier_groups("M-[,S]-Return"), vec!["M-Return", "M-S-Return"]);
+/// assert_eq!(expand_modifier_groups("M-(Return|KP_Enter)"), vec!["M-Return", "M-KP_Enter"]);
+/// assert_eq!(expand_modifier_groups("M-j"), vec!["M-j"]);
+/// ```
+pub fn expand_modifier_groups(pattern: &str) -> Vec<String> {
+    let mut parts: Vec<&str> = pattern.split('-').collect();
+    let key = match parts.pop() {
+        Some(key) => key,
+        None => return vec![pattern.to_string()],
+    };
+    let keys: Vec<&str> = match key.trim().strip_prefix('(') {
+        Some(group) if group.ends_with(')') => {
+            group[..group.len() - 1].split('|').map(str::trim).collect()
+        }
+        _ => vec![key],
+    };
+
+    let mut expanded: Vec<Vec<&str>> = vec![vec![]];
+    for part in parts {
+        let alternatives: Vec<&str> = match part.trim().strip_prefix('[') {
+            Some(group) if group.ends_with(']') => {
+                group[..group.len() - 1].split(',').map(str::trim).collect()
+            }
+            _ => vec![part],
+        };
+
+        expanded = expanded
+            .iter()
+            .flat_map(|prefix| {
+                alternatives.iter().map(move |&alt| {
+                    let mut p = prefix.clone();
+                    if !alt.is_empty() {
+                        p.push(alt);
+                    }
+                    p
+                })
+            })
+            .collect();
+    }
+
+    expanded
+        .iter()
+        .flat_map(|prefix| {
+            keys.iter().map(move |&key| {
+                let mut p = prefix.clone();
+                p.push(key);
+                p.join("-")
+            })
+        })
+        .collect()
+}
+
+/// Find the closest match to a misspelled key name from a list of known key names.
+///
+/// Names are compared using their edit distance and only names that are close enough to be a
+/// likely typo are returned: 'Retrun' suggests 'Return' but 'j' will not suggest 'k'. Ties are
+/// broken alphabetically so that the suggestion is stable.
+///
+/// ```
+/// # use penrose_binding_helpers::suggest_key_name;
+/// let known = ["Return", "Escape", "space"];
+///
+/// assert_eq!(suggest_key_name("Retrun", known.iter().copied()), Some("Return"));
+/// assert_eq!(suggest_key_name("Tav", known.iter().copied()), None);
+/// ```
+pub fn suggest_key_name<'a>(
+    name: &str,
+    known: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max_distance = name.chars().count() / 3;
+
+    known
+        .into_iter()
+        .map(|k| (edit_distance(name, k), k))
+        .filter(|&(d, _)| d > 0 && d <= max_distance)
+        .min()
+        .map(|(_, k)| k)
+}
+
+// The Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + if ca == *cb { 0 } else { 1 };
+            current.push(substitution.min(prev[j + 1] + 1).min(current[j] + 1));
+        }
+        prev = current;
+    }
+
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_codes_are_parsed_in_decimal_hex_and_octal() {
+        let cases = [
+            ("133", Some(133)),
+            ("0x85", Some(133)),
+            ("0X85", Some(133)),
+            ("0o205", Some(133)),
+            (" 44 ", Some(44)),
+            ("255", Some(255)),
+            ("256", None),
+            ("-1", None),
+            ("+1", None),
+            ("0x+1", None),
+            ("j", None),
+            ("", None),
+        ];
+
+        for &(s, expected) in cases.iter() {
+            assert_eq!(parse_key_code(s), expected, "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn templates_are_expanded_for_each_combination_of_key_names() {
+        let ok = |v: &[&str]| Ok(v.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+        let cases: &[(&str, &[&[&str]], Result<Vec<String>, TemplateError>)] = &[
+            ("M-{}", &[&["1", "2"]], ok(&["M-1", "M-2"])),
+            ("{}-Return", &[&["M", "A"]], ok(&["M-Return", "A-Return"])),
+            ("M-F{}", &[&["1", "2"]], ok(&["M-F1", "M-F2"])),
+            ("M-{}", &[&["1", "", " ", "2"]], ok(&["M-1", "M-2"])),
+            (
+                "M-{}-{}",
+                &[&["S", "C"], &["j", "k"]],
+                ok(&["M-S-j", "M-S-k", "M-C-j", "M-C-k"]),
+            ),
+        ];
+
+        for (template, keys, expected) in cases.iter() {
+            assert_eq!(&expand_template(template, keys), expected, "{:?}", template);
+        }
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        use TemplateError::*;
+
+        let cases: &[(&str, &[&[&str]], TemplateError)] = &[
+            ("M-j", &[&["1"]], InvalidPlaceholders("M-j".into(), 1, 0)),
+            (
+                "M-{}-{}",
+                &[&["1"]],
+                InvalidPlaceholders("M-{}-{}".into(), 1, 2),
+            ),
+            (
+                "M-{}",
+                &[&["S"], &["j"]],
+                InvalidPlaceholders("M-{}".into(), 2, 1),
+            ),
+            ("M-{}", &[&[]], NoKeyNames("M-{}".into())),
+            ("M-{}-{}", &[&["S"], &[""]], NoKeyNames("M-{}-{}".into())),
+        ];
+
+        for (template, keys, expected) in cases.iter() {
+            assert_eq!(
+                expand_template(template, keys).as_ref(),
+                Err(expected),
+                "{:?}",
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn key_aliases_are_resolved_ignoring_case() {
+        assert_eq!(resolve_key_alias("Esc"), "Escape");
+        assert_eq!(resolve_key_alias("ESC"), "Escape");
+        assert_eq!(resolve_key_alias("volumeup"), "XF86AudioRaiseVolume");
+        assert_eq!(resolve_key_alias("space"), "space");
+        assert_eq!(resolve_key_alias("j"), "j");
+    }
+
+    #[test]
+    fn key_names_prefer_exact_matches_then_aliases_then_case_insensitive_matches() {
+        let known = ["space", "Return", "ae", "AE", "Escape", "escape"];
+        let find = |name| find_key_name(name, known.iter().copied());
+
+        assert_eq!(find("space"), Some("space"));
+        assert_eq!(find("Space"), Some("space"));
+        assert_eq!(find("enter"), Some("Return"));
+        assert_eq!(find("return"), Some("Return"));
+        assert_eq!(find("AE"), Some("AE"));
+        assert_eq!(find("Ae"), None);
+        assert_eq!(find("Esc"), Some("Escape"));
+        assert_eq!(find("ESCAPE"), None);
+        assert_eq!(find("Tab"), None);
+    }
+
+    #[test]
+    fn edit_distance_counts_single_character_edits() {
+        assert_eq!(edit_distance("Return", "Return"), 0);
+        assert_eq!(edit_distance("Retrun", "Return"), 2);
+        assert_eq!(edit_distance("Escpe", "Escape"), 1);
+        assert_eq!(edit_distance("", "Tab"), 3);
+    }
+}
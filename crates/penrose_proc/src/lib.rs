@@ -15,10 +15,14 @@ mod stub;
 mod validate_bindings;
 
 use stub::stubbed_companion_trait_inner;
-use validate_bindings::validate_user_bindings_inner;
+use validate_bindings::{validate_user_bindings_inner, validate_user_mouse_bindings_inner};
 
 /// This is an internal macro that is used as part of `gen_keybindings` to validate user provided
-/// key bindings at compile time using xmodmap.
+/// key bindings at compile time.
+///
+/// Key names are validated against the static set of X11 keysym names provided by
+/// `penrose_keysyms` rather than the output of xmodmap, so validation does not depend on the
+/// keyboard layout of the machine doing the build.
 ///
 /// It is not intended for use outside of that context and may be modified and updated without
 /// announcing breaking API changes.
@@ -32,9 +36,104 @@ use validate_bindings::validate_user_bindings_inner;
 ///     )
 /// );
 /// ```
+///
+/// The `{}` placeholder can appear anywhere in a template. Templates may also contain more than
+/// one placeholder, in which case a list of key names must be given for each of them and every
+/// combination is validated: `"M-{}-{}"` with `("S", "C") ("j", "k")` expands to `M-S-j`,
+/// `M-S-k`, `M-C-j` and `M-C-k`. The number of placeholders must match the number of lists.
+/// A group of templates that does not expand to any bindings (for example because its list of
+/// key names only contains spacers) is rejected as it is almost certainly a mistake.
+///
+/// An optional third group can be used to enable additional modifiers:
+///
+/// ```no_run
+/// # use penrose_proc::validate_user_bindings;
+/// validate_user_bindings!( ( "H-a" ) () ( "H" ) );
+/// ```
+///
+/// An optional fourth group gives `("alias", "modifiers")` pairs for modifier aliases. Any
+/// modifier in a binding matching an alias is replaced by the modifiers it stands for before the
+/// binding is validated, matching `expand_modifier_aliases` in the main penrose crate:
+///
+/// ```no_run
+/// # use penrose_proc::validate_user_bindings;
+/// validate_user_bindings!( ( "super_shift-j" ) () () ( ("super_shift", "M-S") ) );
+/// ```
+///
+/// Setting `PENROSE_SKIP_KEY_VALIDATION` in the environment at compile time disables the check
+/// for known key names, accepting all key names as valid. Modifiers and duplicate bindings are
+/// still checked.
+///
+/// Setting `PENROSE_XMODMAP_DUMP` to the path of a file containing the output of `xmodmap -pke`
+/// validates key names against the keys listed in that file instead, pinning validation to a
+/// specific keyboard layout without needing an X server at build time.
+///
+/// Friendly aliases for key names (such as `Space` for `space` or `KPEnter` for `KP_Enter`) are
+/// expanded before the key name is checked, matching the aliases accepted by `parse_key_binding`.
+/// Key names that are not known are matched ignoring case if exactly one known name matches.
+///
+/// Multi-key chords (whitespace separated bindings such as `M-x g`) are not supported: they are
+/// only parsed at runtime by `parse_key_chord` in the main penrose crate and are rejected here.
+///
+/// Bindings that shadow common system shortcuts (such as `C-A-F1` for switching virtual terminals)
+/// generate a deprecation warning pointing at the binding. `PENROSE_SYSTEM_SHORTCUTS` replaces the
+/// list of shortcuts that are checked with a comma separated list of bindings and setting
+/// `PENROSE_DENY_SYSTEM_SHORTCUTS` makes them an error instead.
+///
+/// Modifier groups in raw bindings (`M-[S,C]-j`) are expanded to each of the bindings they
+/// represent (`M-S-j` and `M-C-j`) before validation, so each of them is checked for duplicates.
+/// Key name groups (`M-(Return|KP_Enter)`) are expanded in the same way.
+///
+/// Setting `PENROSE_DEBUG_BINDINGS` prints each parsed binding (after expanding templates and
+/// modifier groups) to stderr during compilation, along with where it came from.
+///
+/// Bindings (and templates) starting with `#` are treated as disabled and are not validated.
+///
+/// Starting the input with `const NAME;` (optionally with a visibility) emits a `usize` const
+/// holding the number of enabled bindings after templates and modifier groups have been
+/// expanded. This can be checked against the length of the bindings parsed at runtime:
+///
+/// ```no_run
+/// # use penrose_proc::validate_user_bindings;
+/// validate_user_bindings!( pub const NUM_BINDINGS; ( "M-a", "M-[S,C]-j", "#M-b" ) () );
+/// assert_eq!(NUM_BINDINGS, 3);
+/// ```
 #[proc_macro]
 pub fn validate_user_bindings(input: TokenStream) -> TokenStream {
-    validate_user_bindings_inner(input)
+    validate_user_bindings_inner(input, true)
+}
+
+/// Validate the structure of user provided key bindings without checking that the key names
+/// exist.
+///
+/// This accepts the same input as [validate_user_bindings!] and runs all of the same checks
+/// (modifiers, template shape, duplicate bindings and system shortcuts) apart from the check for
+/// known key names. This is useful when the bindings are being written for a keyboard layout
+/// that differs from the one used for validation. It behaves like [validate_user_bindings!] with
+/// `PENROSE_SKIP_KEY_VALIDATION` set, but only for this invocation.
+///
+/// ```no_run
+/// # use penrose_proc::validate_user_binding_structure;
+/// validate_user_binding_structure!( ( "M-a", "M-SomeCustomKey" ) () );
+/// ```
+#[proc_macro]
+pub fn validate_user_binding_structure(input: TokenStream) -> TokenStream {
+    validate_user_bindings_inner(input, false)
+}
+
+/// This is an internal macro that is used as part of `gen_mousebindings` to validate user
+/// provided mouse bindings at compile time.
+///
+/// It is not intended for use outside of that context and may be modified and updated without
+/// announcing breaking API changes.
+///
+/// ```no_run
+/// # use penrose_proc::validate_user_mouse_bindings;
+/// validate_user_mouse_bindings!("M-Button1", "M-S-Button3");
+/// ```
+#[proc_macro]
+pub fn validate_user_mouse_bindings(input: TokenStream) -> TokenStream {
+    validate_user_mouse_bindings_inner(input)
 }
 
 /// Auto derive a dummy implementation of the marked trait for use in test cases.
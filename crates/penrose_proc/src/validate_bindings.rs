@@ -1,30 +1,207 @@
 //! Compile time validation for user keybindings
+use penrose_binding_helpers::{
+    expand_modifier_groups, expand_template, find_key_name, is_spacer, parse_key_code,
+    suggest_key_name, TemplateError, DISABLED_PREFIX, KEY_CODE_PREFIX, TAP_PREFIX,
+};
 use penrose_keysyms::XKeySym;
 use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2, TokenTree};
+use quote::{quote, quote_spanned};
 use strum::IntoEnumIterator;
 use syn::{
     parenthesized,
     parse::{Parse, ParseStream, Result},
     parse_macro_input,
     punctuated::Punctuated,
-    LitStr, Token,
+    Error, Expr, ExprLit, ExprRange, Ident, Lit, LitStr, RangeLimits, Token, Visibility,
 };
 
-use std::collections::HashSet;
+use std::collections::HashMap;
 
-const VALID_MODIFIERS: [&str; 4] = ["A", "M", "S", "C"];
+// NOTE: this needs to be kept in sync with the modifiers accepted by ModifierKey::try_from in the
+//       main penrose crate.
+const VALID_MODIFIERS: [&str; 8] = ["A", "M", "S", "C", "Alt", "Super", "Shift", "Ctrl"];
+// Modifiers that are supported by ModifierKey but need to be explicitly enabled as they are
+// not mapped on most systems.
+const OPTIONAL_MODIFIERS: [&str; 2] = ["H", "Hyper"];
+// NOTE: this needs to be kept in sync with the buttons accepted by parse_mouse_binding in the main
+//       penrose crate.
+const VALID_BUTTONS: [&str; 5] = ["Button1", "Button2", "Button3", "Button4", "Button5"];
+// If this is set in the environment at compile time then key names are not validated
+const SKIP_KEY_VALIDATION_VAR: &str = "PENROSE_SKIP_KEY_VALIDATION";
+// If this is set in the environment at compile time then key names are validated against the
+// contents of the 'xmodmap -pke' dump at the given path rather than the full set of X keysyms
+const XMODMAP_DUMP_VAR: &str = "PENROSE_XMODMAP_DUMP";
+// If this is set in the environment at compile time then it replaces the default list of system
+// shortcuts that bindings are checked against: a comma separated list of bindings ("" for none)
+const SYSTEM_SHORTCUTS_VAR: &str = "PENROSE_SYSTEM_SHORTCUTS";
+// If this is set in the environment at compile time then bindings that shadow a system shortcut
+// are an error rather than a warning
+const DENY_SYSTEM_SHORTCUTS_VAR: &str = "PENROSE_DENY_SYSTEM_SHORTCUTS";
+// If this is set in the environment at compile time then the parsed bindings are printed to
+// stderr (before validation) to help with debugging templates and modifier groups
+const DEBUG_BINDINGS_VAR: &str = "PENROSE_DEBUG_BINDINGS";
+// Bindings that are commonly handled by the X server or kernel before penrose sees them (or that
+// do something drastic if penrose is not running)
+const SYSTEM_SHORTCUTS: [&str; 15] = [
+    "C-A-BackSpace",
+    "C-A-Delete",
+    "A-Print",
+    "C-A-F1",
+    "C-A-F2",
+    "C-A-F3",
+    "C-A-F4",
+    "C-A-F5",
+    "C-A-F6",
+    "C-A-F7",
+    "C-A-F8",
+    "C-A-F9",
+    "C-A-F10",
+    "C-A-F11",
+    "C-A-F12",
+];
 
 struct Binding {
     raw: String,
+    // The literal this binding was parsed or expanded from, used to point errors at user source
+    span: Span,
     mods: Vec<String>,
     keyname: Option<String>,
+    // The template or modifier group this binding was expanded from (None for literal bindings)
+    template: Option<String>,
+    // Bindings prefixed with '#' are skipped entirely
+    disabled: bool,
+    // 'tap:<modifier>' bindings have a single modifier and no key name
+    tap: bool,
+    // The canonical form of this binding used for duplicate detection
+    normalised: String,
+}
+
+impl Binding {
+    fn new(
+        raw: String,
+        span: Span,
+        mods: Vec<String>,
+        keyname: Option<String>,
+        template: Option<String>,
+        disabled: bool,
+        tap: bool,
+    ) -> Self {
+        let normalised = normalised(&mods, keyname.as_deref(), tap);
+
+        Self {
+            raw,
+            span,
+            mods,
+            keyname,
+            template,
+            disabled,
+            tap,
+            normalised,
+        }
+    }
+
+    // 'super_shift-j' -> 'M-S-j' given ("super_shift", "M-S") as an alias, keeping the raw binding
+    // as written for error messages.
+    // NOTE: this needs to be kept in sync with expand_modifier_aliases in the main penrose crate.
+    fn with_modifier_aliases(self, aliases: &[(String, String)]) -> Self {
+        if self.tap || aliases.is_empty() {
+            return self;
+        }
+
+        let mods = self
+            .mods
+            .iter()
+            .flat_map(|m| match aliases.iter().find(|(alias, _)| alias == m) {
+                Some((_, expansion)) => expansion.split('-').map(|s| s.trim().into()).collect(),
+                None => vec![m.clone()],
+            })
+            .collect();
+
+        let Self {
+            raw,
+            span,
+            keyname,
+            template,
+            disabled,
+            ..
+        } = self;
+
+        Self::new(raw, span, mods, keyname, template, disabled, false)
+    }
+
+    fn origin(&self) -> String {
+        match self.template {
+            Some(ref t) => format!("by the {} '{}'", expansion_kind(t), t),
+            None => "as a literal binding".into(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self.template {
+            Some(ref t) => format!("'{}' (from {} '{}')", self.raw, expansion_kind(t), t),
+            None => format!("'{}'", self.raw),
+        }
+    }
+}
+
+fn expansion_kind(template: &str) -> &'static str {
+    if template.contains("{}") {
+        "template"
+    } else if template.contains('[') {
+        "modifier group"
+    } else {
+        "key group"
+    }
+}
+
+// The optional 'pub const NAME;' prefix naming a const to hold the number of bindings
+struct CountConst(Visibility, Ident);
+
+impl Parse for CountConst {
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        let vis = input.parse()?;
+        input.parse::<Token![const]>()?;
+        let name = input.parse()?;
+        input.parse::<Token![;]>()?;
+
+        Ok(Self(vis, name))
+    }
 }
 
-struct BindingsInput(pub(crate) Vec<Binding>);
+struct BindingsInput(
+    pub(crate) Vec<Binding>,
+    pub(crate) Vec<LitStr>,
+    pub(crate) Option<CountConst>,
+);
+
+impl BindingsInput {
+    // One line per binding giving its canonical form and where it came from
+    fn to_debug_string(&self) -> String {
+        let mut lines = vec![format!("{} bindings parsed:", self.0.len())];
+        lines.extend(self.0.iter().map(|b| {
+            let state = if b.disabled { " (disabled)" } else { "" };
+            format!("  {} <- '{}' {}{}", b.normalised, b.raw, b.origin(), state)
+        }));
+
+        if !self.1.is_empty() {
+            let extra: Vec<String> = self.1.iter().map(LitStr::value).collect();
+            lines.push(format!("extra modifiers: {:?}", extra));
+        }
+
+        lines.join("\n")
+    }
+}
 
 impl Parse for BindingsInput {
     fn parse(input: ParseStream<'_>) -> Result<Self> {
-        let mut bindings = as_bindings(comma_sep_strs(input)?);
+        let count_const = if input.peek(Token![pub]) || input.peek(Token![const]) {
+            Some(input.parse()?)
+        } else {
+            None
+        };
+
+        let mut bindings = as_bindings(comma_sep_strs(input)?, true);
 
         let templated_content;
         parenthesized!(templated_content in input);
@@ -32,132 +209,744 @@ impl Parse for BindingsInput {
         while !templated_content.is_empty() {
             let content;
             parenthesized!(content in templated_content);
-            bindings.extend(expand_templates(
-                comma_sep_strs(&content)?,
-                comma_sep_strs(&content)?,
-            ));
+            // The span of the whole group of templates for reporting empty template blocks
+            let span = content.fork().parse::<TokenTree>()?.span();
+            let templates = comma_sep_strs(&content)?;
+            let mut keynames = vec![comma_sep_keynames(&content)?];
+            while !content.is_empty() {
+                keynames.push(comma_sep_keynames(&content)?);
+            }
+
+            let n_bindings = bindings.len();
+            let no_templates = templates.is_empty();
+            bindings.extend(expand_templates(templates, keynames)?);
+
+            if bindings.len() == n_bindings {
+                let reason = if no_templates {
+                    "no templates were given"
+                } else {
+                    "the list of key names is empty"
+                };
+                return Err(Error::new(
+                    span,
+                    format!("template block does not produce any bindings: {}", reason),
+                ));
+            }
         }
 
-        Ok(Self(bindings))
+        let extra_modifiers = if input.is_empty() {
+            vec![]
+        } else {
+            comma_sep_strs(input)?
+        };
+
+        if !input.is_empty() {
+            let aliases = modifier_aliases(input)?;
+            bindings = bindings
+                .into_iter()
+                .map(|b| b.with_modifier_aliases(&aliases))
+                .collect();
+        }
+
+        Ok(Self(bindings, extra_modifiers, count_const))
     }
 }
 
-fn comma_sep_strs(input: ParseStream<'_>) -> Result<Vec<String>> {
+struct MouseBindingsInput(pub(crate) Vec<Binding>);
+
+impl Parse for MouseBindingsInput {
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        Ok(Self(as_bindings(
+            Punctuated::<LitStr, Token![,]>::parse_terminated(input)?
+                .into_iter()
+                .filter(|s| !is_spacer(&s.value()))
+                .collect(),
+            false,
+        )))
+    }
+}
+
+// Trailing commas are accepted and empty string literals are dropped so that they can be used as
+// spacers when laying out long lists of bindings.
+fn comma_sep_strs(input: ParseStream<'_>) -> Result<Vec<LitStr>> {
     let content;
     parenthesized!(content in input);
     Ok(Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?
-        .iter()
-        .map(LitStr::value)
+        .into_iter()
+        .filter(|s| !is_spacer(&s.value()))
         .collect())
 }
 
-fn as_bindings(raw: Vec<String>) -> Vec<Binding> {
+// Modifier aliases are given as a comma separated list of ("name", "modifiers") pairs
+fn modifier_aliases(input: ParseStream<'_>) -> Result<Vec<(String, String)>> {
+    let content;
+    parenthesized!(content in input);
+    let mut aliases = vec![];
+
+    while !content.is_empty() {
+        let span = content.fork().parse::<TokenTree>()?.span();
+        match comma_sep_strs(&content)?.as_slice() {
+            [alias, expansion] => aliases.push((alias.value(), expansion.value())),
+            _ => {
+                return Err(Error::new(
+                    span,
+                    "modifier aliases must be given as (\"alias\", \"modifiers\") pairs",
+                ))
+            }
+        }
+
+        if !content.is_empty() {
+            content.parse::<Token![,]>()?;
+        }
+    }
+
+    Ok(aliases)
+}
+
+// Key names for templates may be given as string literals or as a range of numeric string
+// literals: "1"..="9" or "0".."5"
+fn comma_sep_keynames(input: ParseStream<'_>) -> Result<Vec<String>> {
+    let content;
+    parenthesized!(content in input);
+    let mut keynames = vec![];
+    for e in Punctuated::<Expr, Token![,]>::parse_terminated(&content)?.iter() {
+        keynames.extend(expand_keyname(e)?.into_iter().filter(|k| !is_spacer(k)));
+    }
+
+    Ok(keynames)
+}
+
+fn expand_keyname(e: &Expr) -> Result<Vec<String>> {
+    match e {
+        Expr::Group(g) => expand_keyname(&g.expr),
+        Expr::Paren(p) => expand_keyname(&p.expr),
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(s), ..
+        }) => Ok(vec![s.value()]),
+        Expr::Range(r) => expand_range(r),
+        _ => Err(Error::new_spanned(
+            e,
+            "key names must be string literals or ranges of numeric string literals",
+        )),
+    }
+}
+
+fn range_bound(e: Option<&Expr>) -> Option<u32> {
+    match e {
+        Some(Expr::Group(g)) => range_bound(Some(&g.expr)),
+        Some(Expr::Lit(ExprLit {
+            lit: Lit::Str(s), ..
+        })) => s.value().parse().ok(),
+        _ => None,
+    }
+}
+
+fn expand_range(r: &ExprRange) -> Result<Vec<String>> {
+    let (from, to) =
+        match (range_bound(r.from.as_deref()), range_bound(r.to.as_deref())) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return Err(Error::new_spanned(
+                r,
+                "invalid key name range: expected numeric string literals such as \"1\"..=\"9\"",
+            )),
+        };
+
+    Ok(match r.limits {
+        RangeLimits::HalfOpen(_) => (from..to).map(|n| n.to_string()).collect(),
+        RangeLimits::Closed(_) => (from..=to).map(|n| n.to_string()).collect(),
+    })
+}
+
+// Modifier groups ('M-[S,C]-j') are only supported for key bindings so mouse bindings are parsed
+// without expanding them, leaving any groups to be rejected as invalid modifiers.
+fn as_bindings(raw: Vec<LitStr>, expand_groups: bool) -> Vec<Binding> {
     raw.iter()
-        .map(|s| {
-            let mut parts: Vec<&str> = s.split('-').collect();
-            let (keyname, mods) = if parts.len() <= 1 {
-                (Some(s.clone()), vec![])
-            } else {
-                (
-                    parts.pop().map(String::from),
-                    parts.into_iter().map(String::from).collect(),
-                )
-            };
+        .flat_map(|lit| {
+            let s = lit.value();
+            if is_disabled(&s) || !expand_groups {
+                return vec![as_binding(s, lit.span(), None)];
+            }
 
-            Binding {
-                raw: s.clone(),
-                keyname,
-                mods,
+            let expanded = expand_modifier_groups(&s);
+            if expanded.len() == 1 && expanded[0] == s {
+                return vec![as_binding(s, lit.span(), None)];
             }
+
+            expanded
+                .into_iter()
+                .map(|e| as_binding(e, lit.span(), Some(s.clone())))
+                .collect()
         })
         .collect()
 }
 
-fn expand_templates(templates: Vec<String>, keynames: Vec<String>) -> Vec<Binding> {
-    templates
+fn as_binding(s: String, span: Span, template: Option<String>) -> Binding {
+    if let Some(modifier) = s.trim().strip_prefix(TAP_PREFIX) {
+        let mods = vec![modifier.trim().to_string()];
+        return Binding::new(s, span, mods, None, template, false, true);
+    }
+
+    let mut parts: Vec<&str> = s.split('-').map(str::trim).collect();
+    let (keyname, mods) = if parts.len() <= 1 {
+        (Some(s.trim().to_string()), vec![])
+    } else {
+        (
+            parts.pop().map(String::from),
+            parts.into_iter().map(String::from).collect(),
+        )
+    };
+    let disabled = is_disabled(&s);
+
+    Binding::new(s, span, mods, keyname, template, disabled, false)
+}
+
+fn is_disabled(raw: &str) -> bool {
+    raw.trim_start().starts_with(DISABLED_PREFIX)
+}
+
+// Each '{}' in a template is replaced by a name from the corresponding list of key names (in
+// order) with every combination of names being generated: ('M-{}-{}', [S, C], [j, k]) ->
+// ['M-S-j', 'M-S-k', 'M-C-j', 'M-C-k']. Placeholders may appear anywhere in the template.
+// Templates that produce no bindings are reported for the template block as a whole by the
+// caller so they expand to nothing here.
+fn expand_templates(templates: Vec<LitStr>, keynames: Vec<Vec<String>>) -> Result<Vec<Binding>> {
+    let keynames: Vec<Vec<&str>> = keynames
         .iter()
-        .flat_map(|t| {
-            let mut parts: Vec<&str> = t.split('-').collect();
-            if parts.pop() != Some("{}") {
-                panic!(
-                    "'{}' is an invalid template: expected '<Modifiers>-{{}}'",
-                    t
-                )
-            };
-            keynames
-                .iter()
-                .map(|k| Binding {
-                    raw: format!("{}-{}", parts.join("-"), k),
-                    mods: parts.iter().map(|m| m.to_string()).collect(),
-                    keyname: Some(k.into()),
-                })
-                .collect::<Vec<Binding>>()
+        .map(|names| names.iter().map(String::as_str).collect())
+        .collect();
+    let keynames: Vec<&[&str]> = keynames.iter().map(Vec::as_slice).collect();
+    let mut bindings = vec![];
+
+    for lit in templates.iter() {
+        let t = lit.value();
+        let expanded = match expand_template(&t, &keynames) {
+            Ok(expanded) => expanded,
+            Err(TemplateError::NoKeyNames(_)) => vec![],
+            Err(e) => return Err(Error::new(lit.span(), e.to_string())),
+        };
+
+        bindings.extend(
+            expanded
+                .into_iter()
+                .map(|raw| as_binding(raw, lit.span(), Some(t.clone()))),
+        );
+    }
+
+    Ok(bindings)
+}
+
+// A canonical representation of a binding that is independent of modifier order, case and
+// whether the short or long form modifier names were used: 'S-super-j' -> 'M-S-j',
+// 'tap:Super' -> 'tap:M'
+fn normalised(mods: &[String], keyname: Option<&str>, tap: bool) -> String {
+    let mut mods: Vec<&str> = mods
+        .iter()
+        .map(|m| match m.to_ascii_lowercase().as_ref() {
+            "a" | "alt" => "A",
+            "m" | "super" => "M",
+            "s" | "shift" => "S",
+            "c" | "ctrl" => "C",
+            "h" | "hyper" => "H",
+            _ => m,
         })
-        .collect()
+        .collect();
+    if tap {
+        return format!("{}{}", TAP_PREFIX, mods.join("-"));
+    }
+
+    mods.sort_unstable();
+    mods.dedup();
+    mods.push(keyname.unwrap_or(""));
+
+    mods.join("-")
 }
 
-fn has_valid_modifiers(binding: &Binding) -> bool {
+// NOTE: key binding modifiers are matched ignoring case (in the same way as parse_key_binding in
+//       the main penrose crate) while mouse binding modifiers are matched exactly.
+fn has_valid_modifiers(binding: &Binding, extra: &[String], ignore_case: bool) -> bool {
+    let matches = |a: &str, b: &str| {
+        if ignore_case {
+            a.eq_ignore_ascii_case(b)
+        } else {
+            a == b
+        }
+    };
+
+    binding.mods.iter().all(|s| {
+        VALID_MODIFIERS
+            .iter()
+            .copied()
+            .chain(extra.iter().map(String::as_str))
+            .any(|m| matches(m, s))
+    })
+}
+
+// Whitespace around each part of a binding has already been trimmed so any that remains is
+// separating the keys of a chord ('M-x g')
+fn is_chord(binding: &Binding) -> bool {
     binding
         .mods
         .iter()
-        .all(|s| VALID_MODIFIERS.contains(&s.as_ref()))
+        .chain(binding.keyname.iter())
+        .any(|part| part.contains(char::is_whitespace))
 }
 
+// Raw key codes ('code:133') are valid as long as they fit in the range of X key codes
 fn is_valid_keyname(binding: &Binding, names: &[String]) -> bool {
-    if let Some(ref k) = binding.keyname {
-        names.contains(&k)
-    } else {
-        false
+    match binding.keyname {
+        Some(ref k) => match k.strip_prefix(KEY_CODE_PREFIX) {
+            Some(code) => parse_key_code(code).is_some(),
+            None => is_known_keyname(k, names),
+        },
+        None => false,
     }
 }
 
-fn report_error(msg: impl AsRef<str>, b: &Binding) {
-    panic!(
-        "'{}' is an invalid key binding: {}\n\
-        Key bindings should be of the form <modifiers>-<key name> or <key name> e.g:  M-j, M-S-slash, M-C-Up, XF86AudioMute",
-        b.raw,
-        msg.as_ref()
+// Key names are matched in the same way as parse_key_binding in the main penrose crate
+fn is_known_keyname(k: &str, names: &[String]) -> bool {
+    find_key_name(k, names.iter().map(String::as_str)).is_some()
+}
+
+fn report_error(msg: impl AsRef<str>, b: &Binding) -> Error {
+    Error::new(
+        b.span,
+        format!(
+            "'{}' is an invalid key binding: {}\n\
+            Key bindings should be of the form <modifiers>-<key name> or <key name> e.g:  M-j, M-S-slash, M-C-Up, XF86AudioMute",
+            b.raw,
+            msg.as_ref()
+        ),
     )
 }
 
-pub(crate) fn validate_user_bindings_inner(input: TokenStream) -> TokenStream {
-    let BindingsInput(mut bindings) = parse_macro_input!(input as BindingsInput);
-    let names: Vec<String> = XKeySym::iter().map(|x| x.as_ref().to_string()).collect();
-    let mut seen = HashSet::new();
+// NOTE: this needs to be kept in sync with parse_xmodmap_output in the main penrose crate.
+fn keynames_from_xmodmap_dump(dump: &str) -> Vec<String> {
+    let mut names: Vec<String> = dump
+        .lines()
+        .flat_map(|line| {
+            // strip out parenthesised annotations: 'adiaeresis (ä)'
+            let mut depth = 0;
+            let stripped: String = line
+                .chars()
+                .filter(|&c| match c {
+                    '(' => {
+                        depth += 1;
+                        false
+                    }
+                    ')' if depth > 0 => {
+                        depth -= 1;
+                        false
+                    }
+                    _ => depth == 0,
+                })
+                .collect();
 
-    for b in bindings.iter_mut() {
-        if seen.contains(&b.raw) {
-            panic!("'{}' is bound as a keybinding more than once", b.raw);
-        } else {
-            seen.insert(&b.raw);
-        }
+            let mut words = stripped.split_whitespace();
+            match (
+                words.next(),
+                words.next().map(str::parse::<u8>),
+                words.next(),
+            ) {
+                (Some("keycode"), Some(Ok(_)), Some("=")) => words.map(String::from).collect(),
+                _ => vec![],
+            }
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+
+    names
+}
+
+fn known_keynames() -> Result<Vec<String>> {
+    match std::env::var_os(XMODMAP_DUMP_VAR) {
+        Some(path) => match std::fs::read_to_string(&path) {
+            Ok(dump) => Ok(keynames_from_xmodmap_dump(&dump)),
+            Err(e) => Err(Error::new(
+                Span::call_site(),
+                format!(
+                    "unable to read xmodmap dump from {} ({:?}): {}",
+                    XMODMAP_DUMP_VAR, path, e
+                ),
+            )),
+        },
+        None => Ok(XKeySym::iter().map(|x| x.as_ref().to_string()).collect()),
+    }
+}
+
+// Errors are reported as compile errors pointing at the offending string literal
+fn into_token_stream(res: Result<TokenStream2>) -> TokenStream {
+    match res {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+// The normalised form of each system shortcut that bindings should be checked against
+fn system_shortcuts() -> Vec<String> {
+    let raw: Vec<String> = match std::env::var(SYSTEM_SHORTCUTS_VAR) {
+        Ok(s) => s.split(',').map(String::from).collect(),
+        Err(_) => SYSTEM_SHORTCUTS.iter().map(|s| s.to_string()).collect(),
+    };
+
+    as_bindings(
+        raw.iter()
+            .filter(|s| !is_spacer(s))
+            .map(|s| LitStr::new(s.trim(), Span::call_site()))
+            .collect(),
+        false,
+    )
+    .into_iter()
+    .map(|b| b.normalised)
+    .collect()
+}
+
+fn shadowed_shortcuts<'a>(bindings: &'a [Binding], shortcuts: &[String]) -> Vec<&'a Binding> {
+    bindings
+        .iter()
+        .filter(|b| !b.disabled && shortcuts.contains(&b.normalised))
+        .collect()
+}
+
+// Stable proc macros can not emit warnings directly so instead we emit a use of a deprecated
+// constant spanned to the offending binding, which the compiler reports as a warning.
+fn check_system_shortcuts(bindings: &[Binding], deny: bool) -> Result<TokenStream2> {
+    let mut warnings = TokenStream2::new();
+
+    for b in shadowed_shortcuts(bindings, &system_shortcuts()) {
+        let msg = format!(
+            "'{}' is commonly used as a system shortcut and may never reach penrose. \
+            Set {} to change the shortcuts that are checked",
+            b.raw, SYSTEM_SHORTCUTS_VAR
+        );
 
-        if b.keyname.is_none() {
-            report_error("no key name specified", b)
+        if deny {
+            return Err(Error::new(b.span, msg));
         }
 
-        if !is_valid_keyname(b, &names) {
-            report_error(
+        warnings.extend(quote_spanned! {b.span=>
+            {
+                #[deprecated(note = #msg)]
+                #[allow(non_upper_case_globals)]
+                const binding_shadows_a_system_shortcut: () = ();
+                let _ = binding_shadows_a_system_shortcut;
+            }
+        });
+    }
+
+    Ok(warnings)
+}
+
+// When check_keys is false only the structure of the bindings is validated (modifiers, template
+// shape and duplicates) with key names being accepted as-is.
+pub(crate) fn validate_user_bindings_inner(input: TokenStream, check_keys: bool) -> TokenStream {
+    let input = parse_macro_input!(input as BindingsInput);
+    if std::env::var_os(DEBUG_BINDINGS_VAR).is_some() {
+        eprintln!("{}", input.to_debug_string());
+    }
+    let BindingsInput(mut bindings, extra_modifiers, count_const) = input;
+
+    // If everything is fine then just consume the input (emitting the binding count if a name
+    // for it was given).
+    //
+    // NOTE: we deliberately do not emit pre-computed key codes here. Modifier masks are specific
+    //       to the X backend being used and key codes depend on the keymap of the machine the
+    //       window manager is run on (not the one it was compiled on) so both need resolving at
+    //       runtime by parse_key_binding. That parse is a one-off cost at startup.
+    let deny = std::env::var_os(DENY_SYSTEM_SHORTCUTS_VAR).is_some();
+    into_token_stream(
+        validate_bindings(&mut bindings, &extra_modifiers, check_keys)
+            .and_then(|_| check_system_shortcuts(&bindings, deny))
+            .map(|warnings| match count_const {
+                Some(CountConst(vis, name)) => {
+                    // Duplicates have been rejected so every enabled binding is distinct
+                    let n = bindings.iter().filter(|b| !b.disabled).count();
+                    quote! { #vis const #name: usize = { #warnings #n }; }
+                }
+                None => warnings,
+            }),
+    )
+}
+
+fn validate_bindings(
+    bindings: &mut [Binding],
+    extra_modifiers: &[LitStr],
+    check_keys: bool,
+) -> Result<()> {
+    for m in extra_modifiers.iter() {
+        if !OPTIONAL_MODIFIERS.contains(&m.value().as_ref()) {
+            return Err(Error::new(
+                m.span(),
                 format!(
-                    "'{}' is not a known key: run 'xmodmap -pke' to see valid key names",
-                    b.keyname.take().unwrap()
+                    "'{}' can not be enabled as a modifier: supported extra modifiers are {:?}",
+                    m.value(),
+                    OPTIONAL_MODIFIERS
                 ),
+            ));
+        }
+    }
+
+    let extra_modifiers: Vec<String> = extra_modifiers.iter().map(LitStr::value).collect();
+    let skip_key_validation = !check_keys || std::env::var_os(SKIP_KEY_VALIDATION_VAR).is_some();
+    let names = if skip_key_validation {
+        vec![]
+    } else {
+        known_keynames()?
+    };
+    let mut seen: HashMap<String, (String, String, String)> = HashMap::new();
+
+    for b in bindings.iter_mut().filter(|b| !b.disabled) {
+        let duplicate = match seen.get(&b.normalised) {
+            Some((raw, origin, _)) if raw == &b.raw && origin == &b.origin() => Some(format!(
+                "'{}' is bound as a keybinding more than once",
+                b.raw
+            )),
+            Some((raw, origin, _)) if raw == &b.raw => Some(format!(
+                "'{}' is bound as a keybinding more than once: {} and {}",
+                b.raw,
+                origin,
+                b.origin()
+            )),
+            Some((_, _, description)) => Some(format!(
+                "{} and {} are the same key binding: modifier order and case do not matter",
+                description,
+                b.describe()
+            )),
+            None => {
+                seen.insert(
+                    b.normalised.clone(),
+                    (b.raw.clone(), b.origin(), b.describe()),
+                );
+                None
+            }
+        };
+
+        if let Some(msg) = duplicate {
+            return Err(Error::new(b.span, msg));
+        }
+
+        if b.keyname.is_none() && !b.tap {
+            return Err(report_error("no key name specified", b));
+        }
+
+        if is_chord(b) {
+            return Err(report_error(
+                "multi-key chords are not supported: only single key bindings can be bound",
                 b,
-            )
+            ));
         }
 
-        if !has_valid_modifiers(b) {
-            report_error(
+        if !b.tap && !skip_key_validation && !is_valid_keyname(b, &names) {
+            let keyname = b.keyname.take().unwrap();
+            let msg = if keyname.starts_with(KEY_CODE_PREFIX) {
+                format!(
+                    "'{}' is not a valid key code: expected '{}<n>' with n in 0..=255 \
+                    (in decimal, hex or octal: 133, 0x85 or 0o205)",
+                    keyname, KEY_CODE_PREFIX
+                )
+            } else {
+                match suggest_key_name(&keyname, names.iter().map(String::as_str)) {
+                    Some(suggestion) => format!(
+                        "'{}' is not a known key: did you mean '{}'?",
+                        keyname, suggestion
+                    ),
+                    None => format!(
+                        "'{}' is not a known key: run 'xmodmap -pke' to see valid key names",
+                        keyname
+                    ),
+                }
+            };
+            return Err(report_error(msg, b));
+        }
+
+        if !has_valid_modifiers(b, &extra_modifiers, true) {
+            return Err(report_error(
                 format!(
                     "'{}' is an invalid modifer set: valid modifiers are {:?}",
                     b.mods.join("-"),
                     VALID_MODIFIERS
+                        .iter()
+                        .copied()
+                        .chain(extra_modifiers.iter().map(String::as_str))
+                        .collect::<Vec<&str>>()
                 ),
                 b,
-            );
+            ));
         }
     }
 
+    Ok(())
+}
+
+pub(crate) fn validate_user_mouse_bindings_inner(input: TokenStream) -> TokenStream {
+    let MouseBindingsInput(bindings) = parse_macro_input!(input as MouseBindingsInput);
+
     // If everything is fine then just consume the input
-    TokenStream::new()
+    into_token_stream(validate_mouse_bindings(&bindings).map(|_| TokenStream2::new()))
+}
+
+fn validate_mouse_bindings(bindings: &[Binding]) -> Result<()> {
+    for b in bindings.iter() {
+        let button = b.keyname.as_deref().unwrap_or("");
+        let msg = if !VALID_BUTTONS.contains(&button) {
+            format!(
+                "'{}' is not a known mouse button: valid buttons are {:?}",
+                button, VALID_BUTTONS
+            )
+        } else if !has_valid_modifiers(b, &[], false) {
+            format!(
+                "'{}' is an invalid modifer set: valid modifiers are {:?}",
+                b.mods.join("-"),
+                VALID_MODIFIERS
+            )
+        } else {
+            continue;
+        };
+
+        return Err(Error::new(
+            b.span,
+            format!(
+                "'{}' is an invalid mouse binding: {}\n\
+                Mouse bindings should be of the form <modifiers>-<button> or <button> e.g:  M-Button1, M-S-Button3",
+                b.raw, msg
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use penrose_binding_helpers::KEY_ALIASES;
+
+    #[test]
+    fn key_aliases_map_to_keysyms_without_shadowing_them() {
+        let keysyms: Vec<String> = XKeySym::iter().map(|x| x.as_ref().to_string()).collect();
+
+        for (alias, keysym) in KEY_ALIASES.iter() {
+            assert!(!keysyms.iter().any(|k| k == alias), "{} is a keysym", alias);
+            assert!(
+                keysyms.iter().any(|k| k == keysym),
+                "{} is not a keysym",
+                keysym
+            );
+        }
+    }
+
+    #[test]
+    fn keynames_are_matched_ignoring_case_when_unambiguous() {
+        let names: Vec<String> = ["space", "Return", "ae", "AE"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        assert!(is_known_keyname("space", &names));
+        assert!(is_known_keyname("Space", &names));
+        assert!(is_known_keyname("SPACE", &names));
+        assert!(is_known_keyname("enter", &names));
+        assert!(is_known_keyname("return", &names));
+        assert!(is_known_keyname("AE", &names));
+        assert!(!is_known_keyname("Ae", &names));
+        assert!(!is_known_keyname("Escape", &names));
+    }
+
+    #[test]
+    fn bindings_shadowing_system_shortcuts_are_found() {
+        let lits = |raw: &[&str]| {
+            raw.iter()
+                .map(|s| LitStr::new(s, Span::call_site()))
+                .collect::<Vec<_>>()
+        };
+        let bindings = as_bindings(
+            lits(&["M-j", "A-C-F1", "#C-A-Delete", "alt-ctrl-BackSpace"]),
+            true,
+        );
+        let shortcuts: Vec<String> = as_bindings(lits(&SYSTEM_SHORTCUTS), false)
+            .into_iter()
+            .map(|b| b.normalised)
+            .collect();
+
+        let shadowed: Vec<&str> = shadowed_shortcuts(&bindings, &shortcuts)
+            .iter()
+            .map(|b| b.raw.as_str())
+            .collect();
+
+        assert_eq!(shadowed, vec!["A-C-F1", "alt-ctrl-BackSpace"]);
+    }
+
+    #[test]
+    fn modifier_groups_are_expanded_for_key_bindings_only() {
+        let lit = || vec![LitStr::new("M-[S,C]-j", Span::call_site())];
+        let raw = |bindings: Vec<Binding>| -> Vec<String> {
+            bindings.into_iter().map(|b| b.raw).collect()
+        };
+
+        assert_eq!(raw(as_bindings(lit(), true)), vec!["M-S-j", "M-C-j"]);
+        assert_eq!(raw(as_bindings(lit(), false)), vec!["M-[S,C]-j"]);
+    }
+
+    #[test]
+    fn modifier_aliases_are_expanded_keeping_the_raw_binding() {
+        let aliases = vec![("super_shift".to_string(), "M-S".to_string())];
+        let expand =
+            |s: &str| as_binding(s.into(), Span::call_site(), None).with_modifier_aliases(&aliases);
+
+        let b = expand("C-super_shift-j");
+        assert_eq!(b.raw, "C-super_shift-j");
+        assert_eq!(b.mods, vec!["C", "M", "S"]);
+        assert_eq!(b.normalised, "C-M-S-j");
+
+        assert_eq!(expand("M-super_shift").normalised, "M-super_shift");
+        assert_eq!(expand("tap:super_shift").mods, vec!["super_shift"]);
+    }
+
+    #[test]
+    fn debug_strings_list_each_binding_and_its_origin() {
+        let lits = |raw: &[&str]| {
+            raw.iter()
+                .map(|s| LitStr::new(s, Span::call_site()))
+                .collect::<Vec<_>>()
+        };
+        let mut bindings = as_bindings(lits(&["S-M-j", "#M-k", "M-[,C]-l"]), true);
+        bindings.extend(
+            expand_templates(lits(&["M-{}"]), vec![vec!["1".into()]]).expect("valid template"),
+        );
+        let input = BindingsInput(bindings, lits(&["H"]), None);
+
+        assert_eq!(
+            input.to_debug_string(),
+            "5 bindings parsed:
+  M-S-j <- 'S-M-j' as a literal binding
+  #M-k <- '#M-k' as a literal binding (disabled)
+  M-l <- 'M-l' by the modifier group 'M-[,C]-l'
+  C-M-l <- 'M-C-l' by the modifier group 'M-[,C]-l'
+  M-1 <- 'M-1' by the template 'M-{}'
+extra modifiers: [\"H\"]"
+        );
+    }
+
+    #[test]
+    fn keynames_are_read_from_xmodmap_dumps() {
+        let dump = "
+keycode   8 =
+keycode  10 = 1 exclam 1 exclam
+keycode  48 = adiaeresis (ä) Adiaeresis (Ä)
+not a keycode line
+keycode  36 = Return NoSymbol Return
+";
+
+        assert_eq!(
+            keynames_from_xmodmap_dump(dump),
+            vec![
+                "1",
+                "Adiaeresis",
+                "NoSymbol",
+                "Return",
+                "adiaeresis",
+                "exclam"
+            ]
+        );
+    }
 }
@@ -14,11 +14,53 @@ fn tests() {
     t.pass("tests/validate_bindings/valid-bindings-are-accepted.rs");
     t.pass("tests/validate_bindings/valid-template-bindings-are-accepted.rs");
     t.pass("tests/validate_bindings/templates-work-with-raw-bindings.rs");
+    t.pass("tests/validate_bindings/valid-keyname-ranges-are-accepted.rs");
+    t.pass("tests/validate_bindings/extra-modifiers-are-accepted.rs");
+    t.pass("tests/validate_bindings/valid-mouse-bindings-are-accepted.rs");
+    t.pass("tests/validate_bindings/whitespace-around-tokens-is-ignored.rs");
+    t.pass("tests/validate_bindings/disabled-bindings-are-skipped.rs");
+    t.pass("tests/validate_bindings/tap-bindings-are-accepted.rs");
+    t.pass("tests/validate_bindings/key-aliases-are-accepted.rs");
+    t.pass("tests/validate_bindings/key-names-are-matched-ignoring-case.rs");
+    t.pass("tests/validate_bindings/modifier-case-is-ignored.rs");
+    t.pass("tests/validate_bindings/trailing-commas-and-spacers-are-accepted.rs");
+    t.pass("tests/validate_bindings/system-shortcuts-are-only-a-warning.rs");
+    t.pass("tests/validate_bindings/modifier-groups-are-expanded.rs");
+    t.pass("tests/validate_bindings/key-groups-are-expanded.rs");
+    t.pass("tests/validate_bindings/modifier-aliases-are-expanded.rs");
+    t.pass("tests/validate_bindings/raw-key-codes-are-accepted.rs");
+    t.pass("tests/validate_bindings/binding-counts-can-be-emitted.rs");
+    t.pass("tests/validate_bindings/placeholders-can-be-anywhere-in-templates.rs");
+    t.pass("tests/validate_bindings/structure-only-validation-accepts-unknown-keys.rs");
     t.compile_fail("tests/validate_bindings/invalid-keys-are-rejected.rs");
     t.compile_fail("tests/validate_bindings/invalid-keys-with-modifiers-are-rejected.rs");
     t.compile_fail("tests/validate_bindings/invalid-modifiers-are-rejected.rs");
     t.compile_fail("tests/validate_bindings/keys-cannot-be-used-as-modifiers.rs");
     t.compile_fail("tests/validate_bindings/invalid-templates-are-rejected.rs");
+    t.compile_fail("tests/validate_bindings/empty-template-blocks-are-rejected.rs");
+    t.compile_fail("tests/validate_bindings/structure-only-validation-checks-structure.rs");
+    t.compile_fail("tests/validate_bindings/template-placeholders-must-match-key-name-lists.rs");
     t.compile_fail("tests/validate_bindings/repeated-bindings-are-rejected.rs");
     t.compile_fail("tests/validate_bindings/bindings-clashing-with-templates-are-rejected.rs");
+    t.compile_fail("tests/validate_bindings/invalid-keyname-ranges-are-rejected.rs");
+    t.compile_fail("tests/validate_bindings/reordered-modifiers-are-rejected.rs");
+    t.compile_fail(
+        "tests/validate_bindings/reordered-modifiers-clashing-with-templates-are-rejected.rs",
+    );
+    t.compile_fail("tests/validate_bindings/extra-modifiers-must-be-enabled.rs");
+    t.compile_fail("tests/validate_bindings/unsupported-extra-modifiers-are-rejected.rs");
+    t.compile_fail("tests/validate_bindings/invalid-mouse-buttons-are-rejected.rs");
+    t.compile_fail("tests/validate_bindings/invalid-mouse-modifiers-are-rejected.rs");
+    t.compile_fail("tests/validate_bindings/invalid-tap-modifiers-are-rejected.rs");
+    t.compile_fail("tests/validate_bindings/modifiers-differing-in-case-are-rejected.rs");
+    t.compile_fail(
+        "tests/validate_bindings/modifier-groups-clashing-with-bindings-are-rejected.rs",
+    );
+    t.compile_fail("tests/validate_bindings/key-groups-clashing-with-bindings-are-rejected.rs");
+    t.compile_fail(
+        "tests/validate_bindings/modifier-aliases-clashing-with-bindings-are-rejected.rs",
+    );
+    t.compile_fail("tests/validate_bindings/out-of-range-key-codes-are-rejected.rs");
+    t.compile_fail("tests/validate_bindings/misspelled-keys-suggest-known-keys.rs");
+    t.compile_fail("tests/validate_bindings/key-chords-are-rejected.rs");
 }
@@ -0,0 +1,22 @@
+// The number of enabled bindings after expansion can be emitted as a const
+use penrose_proc::validate_user_bindings;
+
+validate_user_bindings!(
+    const NUM_BINDINGS;
+    ("M-a", "M-[S,C]-j", "#M-b", "C-A-F1")
+    ((("M-{}", "M-S-{}") ("1", "2", "3")))
+);
+
+mod config {
+    use penrose_proc::validate_user_bindings;
+
+    validate_user_bindings!(pub const NUM_BINDINGS; ("M-a", "tap:M") ());
+}
+
+fn main() {
+    validate_user_bindings!(const LOCAL; ("M-a") ());
+
+    assert_eq!(NUM_BINDINGS, 10);
+    assert_eq!(config::NUM_BINDINGS, 2);
+    assert_eq!(LOCAL, 1);
+}
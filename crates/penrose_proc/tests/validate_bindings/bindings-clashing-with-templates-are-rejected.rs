@@ -1,4 +1,4 @@
-// Bindings that use valid modifiers and valid keys are accepted
+// Literal bindings that are also generated by a template are rejected
 use penrose_proc::validate_user_bindings;
 
 fn main() {
@@ -0,0 +1,11 @@
+// Bindings and templates prefixed with '#' are not validated
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(
+        ("M-a", "#M-a", "#M-notarealkey", " #Q-b")
+        (
+            (("M-{}", "#M-{}", "#Q-{}")("1", "2"))
+        )
+    );
+}
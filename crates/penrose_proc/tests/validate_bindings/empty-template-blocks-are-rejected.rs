@@ -0,0 +1,10 @@
+// Template blocks that do not expand to any bindings are rejected
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(()((("M-{}")())));
+    validate_user_bindings!(()((("M-{}")("", ""))));
+    validate_user_bindings!(()((("M-{}", "M-S-{}")("1".."1"))));
+    validate_user_bindings!(()((("M-{}-{}")("S", "C")())));
+    validate_user_bindings!(()((("")("1", "2"))));
+}
@@ -0,0 +1,6 @@
+// Optional modifiers are accepted when explicitly enabled
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("H-a", "M-Hyper-b")((("H-S-{}")("1", "2")))("H", "Hyper"));
+}
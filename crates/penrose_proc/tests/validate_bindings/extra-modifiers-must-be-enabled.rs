@@ -0,0 +1,6 @@
+// Optional modifiers are rejected unless they have been enabled
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("H-a")());
+}
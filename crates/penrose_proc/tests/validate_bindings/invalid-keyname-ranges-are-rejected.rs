@@ -0,0 +1,6 @@
+// Key name ranges must be made up of numeric string literals
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(()((("M-{}")("a"..="z"))));
+}
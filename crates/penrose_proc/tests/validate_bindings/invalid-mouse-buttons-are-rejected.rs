@@ -0,0 +1,6 @@
+// Mouse buttons other than Button1 to Button5 are rejected
+use penrose_proc::validate_user_mouse_bindings;
+
+fn main() {
+    validate_user_mouse_bindings!("M-Button1", "M-Button9");
+}
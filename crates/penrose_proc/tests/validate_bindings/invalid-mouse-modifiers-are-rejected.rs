@@ -0,0 +1,6 @@
+// Mouse bindings with unknown modifiers are rejected
+use penrose_proc::validate_user_mouse_bindings;
+
+fn main() {
+    validate_user_mouse_bindings!("H-Button1");
+}
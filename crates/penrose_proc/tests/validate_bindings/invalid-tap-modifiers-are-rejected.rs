@@ -0,0 +1,6 @@
+// Tap bindings must name a single valid modifier
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("tap:Q")());
+}
@@ -0,0 +1,6 @@
+// Key name aliases are expanded before validating key names
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("KPEnter", "M-BrightnessUp", "VolumeDown")((("M-{}")("Mute"))));
+}
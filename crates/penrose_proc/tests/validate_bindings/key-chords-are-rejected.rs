@@ -0,0 +1,7 @@
+// Multi-key chords are only supported at runtime so they are rejected by validation
+use penrose_proc::{validate_user_binding_structure, validate_user_bindings};
+
+fn main() {
+    validate_user_bindings!(("M-x g")());
+    validate_user_binding_structure!(("M-x C-g")());
+}
@@ -0,0 +1,6 @@
+// Key name groups expand to one binding for each key name
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("M-(Return|KP_Enter)", "(j|k)", "M-[S,C]-( Up | Down )", "M-j")());
+}
@@ -0,0 +1,8 @@
+// Each key name in a group is checked for duplicates and validated like any other binding
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("M-Return", "M-(Return|KP_Enter)")());
+    validate_user_bindings!(("M-(j|k|j)")());
+    validate_user_bindings!(("M-(j|NotAKey)")());
+}
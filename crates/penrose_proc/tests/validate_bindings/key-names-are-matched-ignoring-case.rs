@@ -0,0 +1,6 @@
+// Key names and aliases are matched ignoring case when there is only one possible match
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("M-Space", "M-S-SPACE", "M-Enter", "M-S-return", "Esc", "C-ESCAPE")());
+}
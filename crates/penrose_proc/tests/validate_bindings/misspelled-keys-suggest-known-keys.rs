@@ -0,0 +1,6 @@
+// Key names that are close to a known key name suggest the known key
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("M-Retrun")());
+}
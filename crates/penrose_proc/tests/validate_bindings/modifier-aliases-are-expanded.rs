@@ -0,0 +1,11 @@
+// Modifier aliases are replaced by the modifiers they stand for before validation
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(
+        ("super_shift-j", "C-super_shift-k", "M-j", "hyper-Return")
+        ((("super_shift-{}") ("1", "2")))
+        ("H")
+        (("super_shift", "M-S"), ("hyper", "H"))
+    );
+}
@@ -0,0 +1,8 @@
+// Aliased bindings are checked for duplicates and invalid modifiers like any other binding
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("M-S-j", "super_shift-j")()()(("super_shift", "M-S")));
+    validate_user_bindings!(("hyper-j")()()(("hyper", "H")));
+    validate_user_bindings!(("super_shift-j")()()(("super_shift")));
+}
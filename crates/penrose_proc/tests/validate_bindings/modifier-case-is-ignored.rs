@@ -0,0 +1,6 @@
+// Modifiers in key bindings are matched ignoring case
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("m-j", "super-SHIFT-k", "m-S-Return")((("a-{}")("1", "2"))));
+}
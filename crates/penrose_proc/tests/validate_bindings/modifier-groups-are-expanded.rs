@@ -0,0 +1,6 @@
+// Modifier groups expand to one binding for each alternative
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("M-[S,C]-j", "M-[,S]-Return", "[M,A]-[S,C]-k", "M-j")());
+}
@@ -0,0 +1,6 @@
+// Each binding a modifier group expands to is checked for duplicates
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("M-[S,C]-j", "M-C-j")());
+}
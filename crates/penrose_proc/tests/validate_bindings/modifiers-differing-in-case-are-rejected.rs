@@ -0,0 +1,6 @@
+// Bindings that only differ in the case of their modifiers are the same binding
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("M-j", "m-j")());
+}
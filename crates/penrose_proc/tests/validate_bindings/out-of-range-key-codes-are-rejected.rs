@@ -0,0 +1,8 @@
+// Raw X key codes must be in the range 0..=255
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("M-code:256")());
+    validate_user_bindings!(("M-code:0x100")());
+    validate_user_bindings!(("M-code:0o8")());
+}
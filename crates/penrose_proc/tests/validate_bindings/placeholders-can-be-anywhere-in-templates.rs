@@ -0,0 +1,13 @@
+// Template placeholders can appear anywhere and templates can have more than one placeholder
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(
+        ()
+        (
+            (("{}-M", "A-{}-Return") ("S", "C"))
+            (("M-F{}") ("1"..="12"))
+            (("M-{}-{}") ("S", "C") ("j", "k"))
+        )
+    );
+}
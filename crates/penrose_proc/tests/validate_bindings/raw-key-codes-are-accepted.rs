@@ -0,0 +1,7 @@
+// Raw X key codes can be used in place of key names
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("code:191", "M-S-code:0", "M-code:255")((("M-{}")("code:10"))));
+    validate_user_bindings!(("code:0x85", "M-code:0XFF", "M-S-code:0o205")());
+}
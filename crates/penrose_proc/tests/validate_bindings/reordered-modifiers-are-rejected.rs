@@ -0,0 +1,6 @@
+// Bindings that only differ in the order of their modifiers are rejected
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("M-S-j", "S-M-j")());
+}
@@ -0,0 +1,7 @@
+// Template bindings that only differ from a literal binding in the order of their modifiers are
+// rejected
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("M-S-1")((("S-M-{}")("1"))));
+}
@@ -0,0 +1,6 @@
+// Unknown key names are accepted when only validating the structure of bindings
+use penrose_proc::validate_user_binding_structure;
+
+fn main() {
+    validate_user_binding_structure!(("M-NotAKey", "M-S-j")((("M-{}")("Custom1", "Custom2"))));
+}
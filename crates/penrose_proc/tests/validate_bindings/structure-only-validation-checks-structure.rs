@@ -0,0 +1,8 @@
+// Modifiers, templates and duplicates are still checked when only validating structure
+use penrose_proc::validate_user_binding_structure;
+
+fn main() {
+    validate_user_binding_structure!(("Q-NotAKey")());
+    validate_user_binding_structure!(("M-NotAKey", "M-NotAKey")());
+    validate_user_binding_structure!(()((("NotATemplate")("Custom1"))));
+}
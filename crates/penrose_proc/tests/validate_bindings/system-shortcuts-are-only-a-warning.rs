@@ -0,0 +1,6 @@
+// Bindings that shadow system shortcuts are accepted with a warning
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("C-A-F1", "M-j")((("A-C-{}")("F2"))));
+}
@@ -0,0 +1,6 @@
+// Modifiers can be bound on their own using 'tap:<modifier>'
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("tap:M", "tap:Alt", "M-a", "tap:H")()("H"));
+}
@@ -0,0 +1,7 @@
+// Each template needs exactly one placeholder for each list of key names
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(()((("M-{}-{}")("1", "2", "3"))));
+    validate_user_bindings!(()((("M-{}")("S", "C")("j", "k"))));
+}
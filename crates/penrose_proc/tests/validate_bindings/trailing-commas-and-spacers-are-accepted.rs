@@ -0,0 +1,14 @@
+// Trailing commas are accepted everywhere and empty strings are ignored
+use penrose_proc::{validate_user_bindings, validate_user_mouse_bindings};
+
+fn main() {
+    validate_user_bindings!(
+        ("M-a", "", "M-b",)
+        (
+            (("M-{}", "", "M-S-{}",)("1", "", "2",))
+            (("A-{}",)("1"..="3",))
+        )
+        ("H", "",)
+    );
+    validate_user_mouse_bindings!("M-Button1", "", "M-Button3",);
+}
@@ -0,0 +1,6 @@
+// Only supported optional modifiers can be enabled
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("X-a")()("X"));
+}
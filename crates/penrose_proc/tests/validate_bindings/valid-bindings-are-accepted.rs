@@ -12,5 +12,11 @@ fn main() {
         "A-S-g",
         "M-S-C-h",
         "M-A-S-C-i",
+        "Super-j",
+        "Alt-k",
+        "Ctrl-l",
+        "Shift-space",
+        "Super-S-m",
+        "XF86AudioRaiseVolume",
     )());
 }
@@ -0,0 +1,8 @@
+// Numeric key name ranges in templates are expanded and accepted
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(()((("M-{}", "M-S-{}")("1"..="9", "0"))((
+        "M-C-{}"
+    )("1".."4"))));
+}
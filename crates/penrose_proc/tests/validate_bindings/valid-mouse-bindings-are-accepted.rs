@@ -0,0 +1,6 @@
+// Mouse bindings using known buttons and modifiers are accepted
+use penrose_proc::validate_user_mouse_bindings;
+
+fn main() {
+    validate_user_mouse_bindings!("Button1", "M-Button1", "M-S-Button3", "Super-Alt-Button5");
+}
@@ -0,0 +1,6 @@
+// Whitespace around modifiers and key names is ignored in the same way as parse_key_binding
+use penrose_proc::validate_user_bindings;
+
+fn main() {
+    validate_user_bindings!(("M- j", "M -k", " A-S-Return ", "space ")((("M - {}")("1", "2"))));
+}
@@ -29,14 +29,14 @@ pub type ExampleKeyBindings = KeyBindings<ExampleXConn>;
 pub type ExampleKeyHandler = KeyEventHandler<ExampleXConn>;
 pub type ExampleMouseBindings = MouseBindings<ExampleXConn>;
 
-pub const EXIT_CODE: KeyCode = KeyCode { mask: 0, code: 0 };
-pub const LAYOUT_CHANGE_CODE: KeyCode = KeyCode { mask: 0, code: 1 };
-pub const WORKSPACE_CHANGE_CODE: KeyCode = KeyCode { mask: 0, code: 2 };
-pub const SCREEN_CHANGE_CODE: KeyCode = KeyCode { mask: 0, code: 3 };
-pub const FOCUS_CHANGE_CODE: KeyCode = KeyCode { mask: 0, code: 4 };
-pub const KILL_CLIENT_CODE: KeyCode = KeyCode { mask: 0, code: 5 };
-pub const ADD_WORKSPACE_CODE: KeyCode = KeyCode { mask: 0, code: 6 };
-pub const CLIENT_TO_WORKSPACE_CODE: KeyCode = KeyCode { mask: 0, code: 7 };
+pub const EXIT_CODE: KeyCode = KeyCode::new(&[], 0);
+pub const LAYOUT_CHANGE_CODE: KeyCode = KeyCode::new(&[], 1);
+pub const WORKSPACE_CHANGE_CODE: KeyCode = KeyCode::new(&[], 2);
+pub const SCREEN_CHANGE_CODE: KeyCode = KeyCode::new(&[], 3);
+pub const FOCUS_CHANGE_CODE: KeyCode = KeyCode::new(&[], 4);
+pub const KILL_CLIENT_CODE: KeyCode = KeyCode::new(&[], 5);
+pub const ADD_WORKSPACE_CODE: KeyCode = KeyCode::new(&[], 6);
+pub const CLIENT_TO_WORKSPACE_CODE: KeyCode = KeyCode::new(&[], 7);
 
 pub fn example_windowmanager(n_screens: u32, events: Vec<XEvent>) -> ExampleWM {
     let conn = ExampleXConn::new(n_screens, events, vec![]);
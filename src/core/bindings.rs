@@ -1,13 +1,26 @@
 //! Setting up and responding to user defined key/mouse bindings
 use crate::{
-    core::{data_types::Point, manager::WindowManager, xconnection::Xid},
+    core::{
+        data_types::Point,
+        manager::WindowManager,
+        xconnection::{XConn, Xid},
+    },
     PenroseError, Result,
 };
 
 #[cfg(feature = "keysyms")]
 use penrose_keysyms::XKeySym;
 
-use std::{collections::HashMap, convert::TryFrom};
+pub use penrose_binding_helpers::{expand_modifier_groups, suggest_key_name, DISABLED_PREFIX};
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    ops::{Range, RangeInclusive},
+    rc::Rc,
+    sync::Mutex,
+};
 
 use strum::EnumIter;
 
@@ -17,6 +30,91 @@ pub type KeyEventHandler<X> = Box<dyn FnMut(&mut WindowManager<X>) -> Result<()>
 /// An action to be run in response to a mouse event
 pub type MouseEventHandler<X> = Box<dyn FnMut(&mut WindowManager<X>, &MouseEvent) -> Result<()>>;
 
+/// Wrap a [KeyEventHandler] so that it only runs on the initial press of its key binding.
+///
+/// By default a binding is run for every autorepeat event sent by the X server while its keys are
+/// held down, which is what you want for actions like resizing or moving between clients. For
+/// actions such as closing a window this makes it easy to trigger them more than once by
+/// accident, so they can be wrapped with `no_repeat` to ignore the repeats:
+///
+/// ```no_run
+/// # #[macro_use] extern crate penrose;
+/// # use penrose::__example_helpers::*;
+/// # use penrose::core::bindings::no_repeat;
+/// # fn example() -> ExampleKeyBindings {
+/// gen_keybindings! {
+///     "M-j" => run_internal!(cycle_client, Forward);
+///     "M-S-q" => no_repeat(run_internal!(kill_client));
+/// }
+/// # }
+/// ```
+pub fn no_repeat<X: XConn + 'static>(mut handler: KeyEventHandler<X>) -> KeyEventHandler<X> {
+    Box::new(move |wm: &mut WindowManager<X>| {
+        if wm.key_press_is_repeat() {
+            Ok(())
+        } else {
+            handler(wm)
+        }
+    })
+}
+
+/// Share a single [KeyEventHandler] between `n` key bindings.
+///
+/// Each of the returned handlers runs `handler` when called, so any state captured by `handler`
+/// is shared between them. This is used by [gen_keybindings][crate::gen_keybindings] to bind the
+/// same action to each of the bindings produced by [expand_modifier_groups].
+pub fn share_key_handler<X: XConn + 'static>(
+    handler: KeyEventHandler<X>,
+    n: usize,
+) -> Vec<KeyEventHandler<X>> {
+    if n == 1 {
+        return vec![handler];
+    }
+
+    let shared = Rc::new(RefCell::new(handler));
+    (0..n)
+        .map(|_| {
+            let handler = Rc::clone(&shared);
+            Box::new(move |wm: &mut WindowManager<X>| (handler.borrow_mut())(wm))
+                as KeyEventHandler<X>
+        })
+        .collect()
+}
+
+/// Replace any modifier in a key binding that matches the name of an alias with the modifiers
+/// that the alias stands for.
+///
+/// `aliases` pairs each alias name with its expansion, written in the same form as the modifiers
+/// of a binding: `("super_shift", "M-S")` allows `super_shift-j` to be used in place of
+/// `M-S-j`. Alias names are matched exactly (ignoring surrounding whitespace) and only the
+/// modifiers of the binding are expanded, never its key name. The expansion is purely textual
+/// so the result still needs to be parsed as normal.
+///
+/// ```
+/// # use penrose::core::bindings::expand_modifier_aliases;
+/// let aliases = [("super_shift", "M-S")];
+/// assert_eq!(expand_modifier_aliases("super_shift-j", &aliases), "M-S-j");
+/// assert_eq!(expand_modifier_aliases("C-super_shift-Return", &aliases), "C-M-S-Return");
+/// assert_eq!(expand_modifier_aliases("M-super_shift", &aliases), "M-super_shift");
+/// ```
+// NOTE: this needs to be kept in sync with with_modifier_aliases in penrose_proc
+pub fn expand_modifier_aliases(pattern: &str, aliases: &[(&str, &str)]) -> String {
+    let mut parts: Vec<&str> = pattern.split('-').collect();
+    let key = parts.pop().unwrap_or_default();
+
+    parts
+        .into_iter()
+        .map(|part| {
+            aliases
+                .iter()
+                .find(|(alias, _)| *alias == part.trim())
+                .map_or(part, |&(_, expansion)| expansion)
+        })
+        .chain(std::iter::once(key))
+        .collect::<Vec<&str>>()
+        .join("-")
+}
+
 /// User defined key bindings
 pub type KeyBindings<X> = HashMap<KeyCode, KeyEventHandler<X>>;
 
@@ -25,6 +123,96 @@ pub type MouseBindings<X> = HashMap<(MouseEventKind, MouseState), MouseEventHand
 
 pub(crate) type CodeMap = HashMap<String, u8>;
 
+/// Enum to store the various ways that parsing a user key binding can fail
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum KeyBindingError {
+    /// The key name was not found in the current keymap
+    #[error("'{0}' is not a known key: run 'xmodmap -pke' to see valid key names")]
+    UnknownKey(String),
+
+    /// The key name was not found in the current keymap but a similarly named key was
+    #[error("'{0}' is not a known key: did you mean '{1}'?")]
+    UnknownKeyWithSuggestion(String, String),
+
+    /// The binding was empty or ended in a '-' so did not include a key name
+    #[error("'{0}' does not specify a key name")]
+    MissingKey(String),
+
+    /// A raw key code ('code:<n>') was not a number in the range 0..=255
+    #[error(
+        "'{0}' is not a valid key code: expected 'code:<n>' with n in 0..=255 \
+        (in decimal, hex or octal: 133, 0x85 or 0o205)"
+    )]
+    InvalidKeyCode(String),
+
+    /// A modifier in the binding was not a known modifier key
+    #[error("'{0}' is not a valid modifier")]
+    UnknownModifier(String),
+
+    /// A [KeyCode] mask (for example one loaded using `serde`) set bits other than the binding
+    /// modifiers, lock modifiers or [TAP_MASK]
+    #[error("{0:#x} is not a valid key code mask: only modifier, lock and tap bits may be set")]
+    InvalidMask(KeyCodeMask),
+
+    /// A key chord did not contain any key bindings
+    #[error("no keys were specified for the key chord")]
+    EmptyChord,
+
+    /// The button name in a mouse binding was not one of Button1 to Button5
+    #[error("'{0}' is not a known mouse button: expected one of Button1 to Button5")]
+    UnknownMouseButton(String),
+
+    /// The direction in a scroll binding was not one of ScrollUp or ScrollDown
+    #[error("'{0}' is not a known scroll direction: expected ScrollUp or ScrollDown")]
+    UnknownScrollDirection(String),
+}
+
+/// Something that can be expanded into one or more key names for use in the `map` block of
+/// [gen_keybindings][crate::gen_keybindings].
+///
+/// Implemented for string literals and ranges of numeric string literals so that `"1"..="9"` can
+/// be used in place of listing out each key name individually.
+///
+/// # Panics
+/// The range implementations will panic if either end of the range is not a number.
+/// `gen_keybindings` validates this at compile time.
+pub trait IntoKeyNames {
+    /// Expand this value into the key names it represents
+    fn into_key_names(self) -> Vec<String>;
+}
+
+// Empty key names are used as spacers when laying out map blocks.
+impl IntoKeyNames for &str {
+    fn into_key_names(self) -> Vec<String> {
+        if penrose_binding_helpers::is_spacer(self) {
+            vec![]
+        } else {
+            vec![self.to_string()]
+        }
+    }
+}
+
+fn numeric_range_bound(s: &str) -> u32 {
+    s.parse()
+        .unwrap_or_else(|_| panic!("'{}' is not a valid numeric key name range bound", s))
+}
+
+impl IntoKeyNames for Range<&str> {
+    fn into_key_names(self) -> Vec<String> {
+        (numeric_range_bound(self.start)..numeric_range_bound(self.end))
+            .map(|n| n.to_string())
+            .collect()
+    }
+}
+
+impl IntoKeyNames for RangeInclusive<&str> {
+    fn into_key_names(self) -> Vec<String> {
+        (numeric_range_bound(self.start())..=numeric_range_bound(self.end()))
+            .map(|n| n.to_string())
+            .collect()
+    }
+}
+
 /// Abstraction layer for working with key presses
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum KeyPress {
@@ -82,17 +270,181 @@ pub type KeyCodeMask = u16;
 /// A u8 X key-code enum value
 pub type KeyCodeValue = u8;
 
+/// The X modifier mask set while CapsLock is active
+pub const CAPS_LOCK_MASK: KeyCodeMask = 1 << 1;
+
+/// The X modifier bits that can be used in key bindings: Shift, Control, Alt (mod1), Hyper (mod3)
+/// and Super (mod4).
+///
+/// Everything else (lock state, mod5 / AltGr and held mouse buttons) is dropped by
+/// [KeyCode::normalised].
+pub const BINDING_MODIFIER_MASK: KeyCodeMask = 1 | 1 << 2 | 1 << 3 | 1 << 5 | 1 << 6;
+
+/// A mask bit that is never set by the X server, used to mark a [KeyCode] as being bound to
+/// tapping a modifier key on its own (a `tap:` binding) rather than pressing it.
+pub const TAP_MASK: KeyCodeMask = 1 << 15;
+
+/// The X modifier mask (mod2) set while NumLock is active
+pub const NUM_LOCK_MASK: KeyCodeMask = 1 << 4;
+
+/// Each combination of lock modifiers that can be active when a binding is pressed. Bindings need
+/// grabbing with each of these in order to fire regardless of the NumLock and CapsLock state.
+pub(crate) const LOCK_MASK_COMBINATIONS: [KeyCodeMask; 4] = [
+    0,
+    NUM_LOCK_MASK,
+    CAPS_LOCK_MASK,
+    NUM_LOCK_MASK | CAPS_LOCK_MASK,
+];
+
 /// A key press and held modifiers
+///
+/// A [KeyCode] is either built from a set of [ModifierKey]s using [KeyCode::new] or parsed from
+/// a user friendly binding (see [gen_keybindings][crate::gen_keybindings]) so that it is not
+/// possible to end up with a modifier mask that can never match a key press.
+///
+/// With the `serde` feature enabled a [KeyCode] can be serialised, allowing resolved bindings to
+/// be cached between runs. Key codes depend on the keyboard layout so cached codes should be
+/// checked against the current keymap (e.g. using [name_for_code][1]) before being used.
+/// Deserialising a [KeyCode] whose mask sets any bits other than those in
+/// [BINDING_MODIFIER_MASK], [NUM_LOCK_MASK], [CAPS_LOCK_MASK] or [TAP_MASK] is an error.
+///
+/// [1]: crate::xcb::helpers::name_for_code
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawKeyCode"))]
 pub struct KeyCode {
-    /// The held modifier mask
-    pub mask: KeyCodeMask,
-    /// The key code that was held
-    pub code: KeyCodeValue,
+    mask: KeyCodeMask,
+    code: KeyCodeValue,
+}
+
+// The serialised form of a KeyCode, validated by KeyCode::try_from before it is accepted
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct RawKeyCode {
+    mask: KeyCodeMask,
+    code: KeyCodeValue,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawKeyCode> for KeyCode {
+    type Error = KeyBindingError;
+
+    fn try_from(raw: RawKeyCode) -> std::result::Result<Self, Self::Error> {
+        let valid = BINDING_MODIFIER_MASK | NUM_LOCK_MASK | CAPS_LOCK_MASK | TAP_MASK;
+        if raw.mask & !valid != 0 {
+            return Err(KeyBindingError::InvalidMask(raw.mask));
+        }
+
+        Ok(KeyCode {
+            mask: raw.mask,
+            code: raw.code,
+        })
+    }
+}
+
+// The core X protocol modifier bit for each ModifierKey. Hyper is assumed to be mapped to mod3.
+const fn modifier_bit(m: ModifierKey) -> KeyCodeMask {
+    match m {
+        ModifierKey::Shift => 1,
+        ModifierKey::Ctrl => 1 << 2,
+        ModifierKey::Alt => 1 << 3,
+        ModifierKey::Hyper => 1 << 5,
+        ModifierKey::Meta => 1 << 6,
+    }
+}
+
+/// The X modifier mask used for each [ModifierKey] when parsing key bindings.
+///
+/// The default mapping matches the most common X setup: Alt on mod1, Hyper on mod3 and Super on
+/// mod4 (along with the fixed Shift and Control bits). If your modifiers are mapped differently
+/// (check the output of `xmodmap -pm`) then a [ModifierMap] can be given to
+/// [parse_key_binding_with_modifiers][1] or set for [gen_keybindings][2] using a `modifier_map`
+/// block. Only the bits in [BINDING_MODIFIER_MASK] are used when matching key presses.
+///
+/// ```
+/// # use penrose::core::bindings::ModifierMap;
+/// // Super is mapped to mod3 rather than mod4
+/// let modifiers = ModifierMap { meta: 1 << 5, ..Default::default() };
+/// ```
+///
+/// [1]: crate::xcb::helpers::parse_key_binding_with_modifiers
+/// [2]: crate::gen_keybindings
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ModifierMap {
+    /// The mask used for [ModifierKey::Shift]
+    pub shift: KeyCodeMask,
+    /// The mask used for [ModifierKey::Ctrl]
+    pub ctrl: KeyCodeMask,
+    /// The mask used for [ModifierKey::Alt]
+    pub alt: KeyCodeMask,
+    /// The mask used for [ModifierKey::Meta]
+    pub meta: KeyCodeMask,
+    /// The mask used for [ModifierKey::Hyper]
+    pub hyper: KeyCodeMask,
+}
+
+impl Default for ModifierMap {
+    fn default() -> Self {
+        Self {
+            shift: modifier_bit(ModifierKey::Shift),
+            ctrl: modifier_bit(ModifierKey::Ctrl),
+            alt: modifier_bit(ModifierKey::Alt),
+            meta: modifier_bit(ModifierKey::Meta),
+            hyper: modifier_bit(ModifierKey::Hyper),
+        }
+    }
+}
+
+impl ModifierMap {
+    /// The mask to use for the given [ModifierKey]
+    pub fn mask(&self, m: ModifierKey) -> KeyCodeMask {
+        match m {
+            ModifierKey::Shift => self.shift,
+            ModifierKey::Ctrl => self.ctrl,
+            ModifierKey::Alt => self.alt,
+            ModifierKey::Meta => self.meta,
+            ModifierKey::Hyper => self.hyper,
+        }
+    }
 }
 
 impl KeyCode {
+    /// Construct a [KeyCode] for the given key code being pressed while `mods` are held.
+    ///
+    /// ```
+    /// # use penrose::core::bindings::{KeyCode, ModifierKey};
+    /// let k = KeyCode::new(&[ModifierKey::Meta, ModifierKey::Shift], 44);
+    ///
+    /// assert_eq!(k.code(), 44);
+    /// assert_eq!(k.mask(), 1 << 6 | 1);
+    /// ```
+    pub const fn new(mods: &[ModifierKey], code: KeyCodeValue) -> KeyCode {
+        let mut mask = 0;
+        let mut i = 0;
+        while i < mods.len() {
+            mask |= modifier_bit(mods[i]);
+            i += 1;
+        }
+
+        KeyCode { mask, code }
+    }
+
+    // Masks reported by the X server or computed by the binding parsers are used as is
+    #[cfg_attr(not(any(feature = "xcb", feature = "x11rb")), allow(dead_code))]
+    pub(crate) const fn from_raw(mask: KeyCodeMask, code: KeyCodeValue) -> KeyCode {
+        KeyCode { mask, code }
+    }
+
+    /// The held modifier mask
+    pub fn mask(&self) -> KeyCodeMask {
+        self.mask
+    }
+
+    /// The key code that was held
+    pub fn code(&self) -> KeyCodeValue {
+        self.code
+    }
+
     /// Create a new [KeyCode] from this one that removes the given mask
     pub fn ignoring_modifier(&self, mask: KeyCodeMask) -> KeyCode {
         KeyCode {
@@ -100,6 +452,187 @@ impl KeyCode {
             code: self.code,
         }
     }
+
+    /// Create a new [KeyCode] from this one with its mask replaced by the given mask.
+    ///
+    /// Only the bits of `mask` that can be used in bindings (see [BINDING_MODIFIER_MASK]) are
+    /// kept.
+    pub fn with_mask(self, mask: KeyCodeMask) -> KeyCode {
+        KeyCode {
+            mask: mask & BINDING_MODIFIER_MASK,
+            ..self
+        }
+    }
+
+    /// The modifier bits that differ between this [KeyCode] and `other`.
+    ///
+    /// Useful when rebinding a key to work out which modifier combinations need to be ungrabbed
+    /// and grabbed again.
+    pub fn mask_diff(&self, other: &KeyCode) -> KeyCodeMask {
+        self.mask ^ other.mask
+    }
+
+    /// Create a new [KeyCode] from this one that removes the NumLock and CapsLock modifiers
+    pub fn ignoring_locks(&self) -> KeyCode {
+        self.ignoring_modifier(NUM_LOCK_MASK | CAPS_LOCK_MASK)
+    }
+
+    /// Create a new [KeyCode] from this one that only keeps the modifiers that can be used in
+    /// key bindings (see [BINDING_MODIFIER_MASK]).
+    ///
+    /// This gives the same [KeyCode] for a key regardless of the current lock state, making it
+    /// a reliable [HashMap] key when comparing bindings with one another.
+    pub fn normalised(&self) -> KeyCode {
+        KeyCode {
+            mask: self.mask & BINDING_MODIFIER_MASK,
+            code: self.code,
+        }
+    }
+
+    /// Create a new [KeyCode] from this one that keeps the modifiers that can be used in key
+    /// bindings (see [BINDING_MODIFIER_MASK]) along with NumLock and CapsLock.
+    ///
+    /// Key presses received from the X server are normalised in this way before being used to
+    /// look up the bound action so that bindings which explicitly include a lock modifier (see
+    /// [KeyCode::uses_lock_modifier]) can be told apart from those that ignore the lock state.
+    pub fn normalised_keeping_locks(&self) -> KeyCode {
+        KeyCode {
+            mask: self.mask & (BINDING_MODIFIER_MASK | NUM_LOCK_MASK | CAPS_LOCK_MASK),
+            code: self.code,
+        }
+    }
+
+    /// Whether this [KeyCode] and `other` would be triggered by the same key press.
+    ///
+    /// Key codes conflict when they are for the same key and have the same mask once the bits
+    /// that are ignored when matching bindings (see [KeyCode::normalised]) have been dropped. A
+    /// `tap:` binding conflicts with a binding for the same modifier key pressed on its own as
+    /// both are grabbed in the same way.
+    pub fn conflicts_with(&self, other: &KeyCode) -> bool {
+        self.normalised() == other.normalised()
+    }
+
+    /// The [KeyCode] used to bind tapping this key on its own (see [TAP_MASK])
+    pub fn as_tap(&self) -> KeyCode {
+        KeyCode {
+            mask: TAP_MASK,
+            code: self.code,
+        }
+    }
+
+    /// Whether this [KeyCode] is bound to tapping a modifier key rather than a key press
+    pub fn is_tap(&self) -> bool {
+        self.mask & TAP_MASK > 0
+    }
+
+    /// Check whether a key press with the given mask and code should trigger this [KeyCode],
+    /// ignoring the state of NumLock and CapsLock
+    ///
+    /// Lock modifiers that are explicitly set for this [KeyCode] (see
+    /// [KeyCode::uses_lock_modifier]) are not ignored: they need to be active in the key press.
+    pub fn matches(&self, received_mask: KeyCodeMask, code: KeyCodeValue) -> bool {
+        let received = KeyCode {
+            mask: received_mask,
+            code,
+        };
+        let ignored = (NUM_LOCK_MASK | CAPS_LOCK_MASK) & !self.mask;
+
+        self.ignoring_modifier(ignored) == received.ignoring_modifier(ignored)
+    }
+
+    /// Whether the mask of this [KeyCode] explicitly includes NumLock or CapsLock.
+    ///
+    /// Bindings normally fire regardless of the lock state, so every combination of the lock
+    /// modifiers is grabbed for each binding and they are ignored when looking up the binding
+    /// for a key press. A [KeyCode] that deliberately includes a lock modifier (for example one
+    /// loaded using `serde`) is instead only grabbed and matched with that lock active, taking
+    /// priority over a binding for the same key that ignores the lock state.
+    pub fn uses_lock_modifier(&self) -> bool {
+        self.mask & (NUM_LOCK_MASK | CAPS_LOCK_MASK) != 0
+    }
+
+    // The bindings that a key press (normalised using normalised_keeping_locks) could be for,
+    // most specific first: the binding for exactly the active lock modifiers followed by those
+    // for each subset of them, down to the binding that ignores the lock state entirely. Each of
+    // these matches the key press (see KeyCode::matches).
+    pub(crate) fn binding_candidates(&self) -> impl Iterator<Item = KeyCode> {
+        let locks = self.mask & (NUM_LOCK_MASK | CAPS_LOCK_MASK);
+        let key = self.ignoring_locks();
+        LOCK_MASK_COMBINATIONS
+            .iter()
+            .rev()
+            .copied()
+            .filter(move |m| m & locks == *m)
+            .map(move |m| KeyCode {
+                mask: key.mask | m,
+                ..key
+            })
+    }
+
+    // The lock modifier combinations that this key needs grabbing with: all of them unless some
+    // of the lock modifiers are explicitly set, in which case only those including them.
+    #[cfg_attr(not(any(feature = "xcb", feature = "x11rb")), allow(dead_code))]
+    pub(crate) fn lock_mask_combinations(&self) -> impl Iterator<Item = KeyCodeMask> {
+        let locks = self.mask & (NUM_LOCK_MASK | CAPS_LOCK_MASK);
+        LOCK_MASK_COMBINATIONS
+            .iter()
+            .copied()
+            .filter(move |m| m & locks == locks)
+    }
+}
+
+/// Tracks which keys are currently held down so that autorepeat key presses can be told apart
+/// from the initial press of a key.
+///
+/// By default the X server reports an autorepeat as a key release immediately followed by a key
+/// press with the same timestamp. If detectable autorepeat has been enabled then only the
+/// repeated key presses are sent. Both forms are treated as repeats.
+#[cfg_attr(not(any(feature = "xcb", feature = "x11rb")), allow(dead_code))]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct KeyRepeatTracker {
+    held: HashSet<KeyCodeValue>,
+    last_release: Option<(KeyCodeValue, u32)>,
+}
+
+#[cfg_attr(not(any(feature = "xcb", feature = "x11rb")), allow(dead_code))]
+impl KeyRepeatTracker {
+    /// Record a key press at the given X timestamp, returning true if it is an autorepeat
+    pub(crate) fn press(&mut self, code: KeyCodeValue, time: u32) -> bool {
+        let repeat = self.held.contains(&code) || self.last_release == Some((code, time));
+        self.held.insert(code);
+        self.last_release = None;
+
+        repeat
+    }
+
+    /// Record a key release at the given X timestamp
+    pub(crate) fn release(&mut self, code: KeyCodeValue, time: u32) {
+        self.held.remove(&code);
+        self.last_release = Some((code, time));
+    }
+}
+
+/// A sequence of [KeyCode]s that need to be pressed one after another, such as `M-x g`
+///
+/// Chords can be parsed (see [parse_key_chord][1]) but are not yet dispatched by the
+/// [WindowManager][2].
+///
+/// [1]: crate::xcb::helpers::parse_key_chord
+/// [2]: crate::core::manager::WindowManager
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct KeyChord(pub Vec<KeyCode>);
+
+impl KeyChord {
+    /// The [KeyCode]s making up this chord in the order they need to be pressed
+    pub fn codes(&self) -> &[KeyCode] {
+        &self.0
+    }
+
+    /// Check whether the given key presses are a (possibly complete) prefix of this chord
+    pub fn starts_with(&self, pressed: &[KeyCode]) -> bool {
+        self.0.starts_with(pressed)
+    }
 }
 
 /// Known mouse buttons for binding actions
@@ -130,6 +663,38 @@ impl From<MouseButton> for u8 {
     }
 }
 
+/// The direction of a scroll wheel event.
+///
+/// X reports scrolling as presses of mouse buttons 4 and 5 so each direction corresponds to one
+/// of the scroll [MouseButton]s.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ScrollDirection {
+    /// Button 4
+    Up,
+    /// Button 5
+    Down,
+}
+
+impl ScrollDirection {
+    /// The X button mask that is set in the modifier state while this scroll button is held
+    pub fn button_mask(&self) -> KeyCodeMask {
+        match self {
+            ScrollDirection::Up => 1 << 11,
+            ScrollDirection::Down => 1 << 12,
+        }
+    }
+}
+
+impl From<ScrollDirection> for MouseButton {
+    fn from(d: ScrollDirection) -> MouseButton {
+        match d {
+            ScrollDirection::Up => MouseButton::ScrollUp,
+            ScrollDirection::Down => MouseButton::ScrollDown,
+        }
+    }
+}
+
 /// Known modifier keys for bindings
 #[derive(Debug, EnumIter, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -142,6 +707,36 @@ pub enum ModifierKey {
     Shift,
     /// Meta / super / windows
     Meta,
+    /// Hyper (mod3): not mapped by default on most systems
+    Hyper,
+}
+
+impl ModifierKey {
+    /// Parse the single character short form of a modifier as used in key bindings: 'C', 'A',
+    /// 'S', 'M' or 'H'
+    pub fn from_char(c: char) -> Option<ModifierKey> {
+        match c {
+            'C' => Some(Self::Ctrl),
+            'A' => Some(Self::Alt),
+            'S' => Some(Self::Shift),
+            'M' => Some(Self::Meta),
+            'H' => Some(Self::Hyper),
+            _ => None,
+        }
+    }
+
+    /// Parse a modifier name in the same way as [ModifierKey::try_from] but ignoring case, so
+    /// that 'm', 'M', 'super' and 'SUPER' are all accepted as [ModifierKey::Meta].
+    pub fn try_from_ignoring_case(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_ref() {
+            "c" | "ctrl" => Ok(Self::Ctrl),
+            "a" | "alt" => Ok(Self::Alt),
+            "s" | "shift" => Ok(Self::Shift),
+            "m" | "super" => Ok(Self::Meta),
+            "h" | "hyper" => Ok(Self::Hyper),
+            _ => Err(PenroseError::UnknownModifier(s.into())),
+        }
+    }
 }
 
 impl TryFrom<&str> for ModifierKey {
@@ -149,10 +744,11 @@ impl TryFrom<&str> for ModifierKey {
 
     fn try_from(s: &str) -> Result<Self> {
         match s {
-            "C" => Ok(Self::Ctrl),
-            "A" => Ok(Self::Alt),
-            "S" => Ok(Self::Shift),
-            "M" => Ok(Self::Meta),
+            "C" | "Ctrl" => Ok(Self::Ctrl),
+            "A" | "Alt" => Ok(Self::Alt),
+            "S" | "Shift" => Ok(Self::Shift),
+            "M" | "Super" => Ok(Self::Meta),
+            "H" | "Hyper" => Ok(Self::Hyper),
             _ => Err(PenroseError::UnknownModifier(s.into())),
         }
     }
@@ -176,6 +772,34 @@ impl MouseState {
     }
 }
 
+/**
+ * Convert user friendly mouse bindings into a [MouseState].
+ *
+ * Bindings use the same modifier syntax as [gen_keybindings][crate::gen_keybindings] with the
+ * button given as one of 'Button1' to 'Button5': 'M-Button1', 'M-S-Button3'.
+ */
+pub fn parse_mouse_binding(
+    pattern: impl AsRef<str>,
+) -> std::result::Result<MouseState, KeyBindingError> {
+    let mut parts: Vec<&str> = pattern.as_ref().split('-').map(str::trim).collect();
+    let name = parts.remove(parts.len() - 1);
+    let button = match name {
+        "Button1" => MouseButton::Left,
+        "Button2" => MouseButton::Middle,
+        "Button3" => MouseButton::Right,
+        "Button4" => MouseButton::ScrollUp,
+        "Button5" => MouseButton::ScrollDown,
+        _ => return Err(KeyBindingError::UnknownMouseButton(name.into())),
+    };
+
+    let modifiers = parts
+        .iter()
+        .map(|&s| ModifierKey::try_from(s).map_err(|_| KeyBindingError::UnknownModifier(s.into())))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(MouseState::new(button, modifiers))
+}
+
 /// The types of mouse events represented by a MouseEvent
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -224,3 +848,443 @@ impl MouseEvent {
         }
     }
 }
+
+// The keys currently grabbed on each root window. X does not provide a way to query the passive
+// grabs held by a client so they are recorded by the backends as keys are grabbed and ungrabbed.
+static GRABBED_KEYS: Mutex<Vec<(Xid, KeyCode)>> = Mutex::new(Vec::new());
+
+/**
+ * The keys that penrose currently has grabbed on the given root window, in the order they were
+ * grabbed.
+ *
+ * The X server does not provide a way to list the passive key grabs held by a client, so this
+ * reflects the keys that have been grabbed by either the xcb or x11rb backend (including the
+ * startup bindings and, for xcb, any grabbed using [grab_key][1]) and not since released.
+ * Comparing this against your intended bindings shows any that were never grabbed. Each key is
+ * returned in its normalised form (see [KeyCode::normalised]).
+ *
+ * [1]: crate::xcb::helpers::grab_key
+ */
+pub fn grabbed_keys(root: Xid) -> Vec<KeyCode> {
+    GRABBED_KEYS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter(|(r, _)| *r == root)
+        .map(|&(_, k)| k)
+        .collect()
+}
+
+#[cfg_attr(not(any(feature = "xcb", feature = "x11rb")), allow(dead_code))]
+pub(crate) fn record_grab(root: Xid, key: &KeyCode) {
+    let key = key.normalised();
+    let mut grabbed = GRABBED_KEYS.lock().unwrap_or_else(|e| e.into_inner());
+    if !grabbed.contains(&(root, key)) {
+        grabbed.push((root, key));
+    }
+}
+
+#[cfg_attr(not(feature = "xcb"), allow(dead_code))]
+pub(crate) fn forget_grab(root: Xid, key: &KeyCode) {
+    let key = key.normalised();
+    GRABBED_KEYS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|&(r, k)| (r, k) != (root, key));
+}
+
+// Used when all key grabs for a root window are dropped at once
+#[cfg_attr(not(any(feature = "xcb", feature = "x11rb")), allow(dead_code))]
+pub(crate) fn forget_all_grabs(root: Xid) {
+    GRABBED_KEYS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|&(r, _)| r != root);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each test uses its own root window ID as the registry of grabbed keys is shared
+    #[test]
+    fn grabbed_keys_are_recorded_and_forgotten() {
+        let root = 1001;
+        let j = KeyCode::from_raw(1 << 6, 44);
+        let k = KeyCode::from_raw(1 << 6, 45);
+
+        record_grab(root, &j);
+        record_grab(root, &k);
+        record_grab(root + 1, &j);
+        assert_eq!(grabbed_keys(root), vec![j, k]);
+
+        forget_grab(root, &j);
+        assert_eq!(grabbed_keys(root), vec![k]);
+        assert_eq!(grabbed_keys(root + 1), vec![j]);
+
+        forget_all_grabs(root);
+        assert_eq!(grabbed_keys(root), vec![]);
+        assert_eq!(grabbed_keys(root + 1), vec![j]);
+    }
+
+    #[test]
+    fn grabbed_keys_are_normalised_and_deduplicated() {
+        let root = 2001;
+        let j = KeyCode::from_raw(1 << 6, 44);
+
+        record_grab(root, &j);
+        record_grab(root, &KeyCode::from_raw((1 << 6) | CAPS_LOCK_MASK, 44));
+        assert_eq!(grabbed_keys(root), vec![j]);
+
+        forget_grab(root, &KeyCode::from_raw((1 << 6) | NUM_LOCK_MASK, 44));
+        assert_eq!(grabbed_keys(root), vec![]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn key_codes_round_trip_through_serde() {
+        let k = KeyCode::new(&[ModifierKey::Meta, ModifierKey::Shift], 44);
+        let json = serde_json::to_string(&k).unwrap();
+
+        assert_eq!(json, r#"{"mask":65,"code":44}"#);
+        assert_eq!(serde_json::from_str::<KeyCode>(&json).unwrap(), k);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn key_codes_with_lock_or_tap_bits_round_trip_through_serde() {
+        let k = KeyCode::new(&[ModifierKey::Meta], 44);
+        for &mask in [NUM_LOCK_MASK, CAPS_LOCK_MASK].iter() {
+            let locked = KeyCode::from_raw(k.mask() | mask, 44);
+            let json = serde_json::to_string(&locked).unwrap();
+            assert_eq!(serde_json::from_str::<KeyCode>(&json).unwrap(), locked);
+        }
+
+        let tap = k.as_tap();
+        let json = serde_json::to_string(&tap).unwrap();
+        assert_eq!(serde_json::from_str::<KeyCode>(&json).unwrap(), tap);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn key_codes_with_invalid_masks_are_rejected_by_serde() {
+        let invalid = KeyCode::from_raw(64 | 1 << 12, 44);
+        let json = serde_json::to_string(&invalid).unwrap();
+        let err = serde_json::from_str::<KeyCode>(&json).unwrap_err();
+
+        assert!(err
+            .to_string()
+            .contains("0x1040 is not a valid key code mask"));
+    }
+
+    test_cases! {
+        modifier_aliases;
+        args: (pattern: &str, expected: &str);
+
+        case: single_alias => ("super_shift-j", "M-S-j");
+        case: alias_with_modifiers => ("C-super_shift-j", "C-M-S-j");
+        case: multiple_aliases => ("super_shift-hyper-j", "M-S-H-j");
+        case: surrounding_whitespace => (" super_shift - j", "M-S- j");
+        case: key_names_are_not_expanded => ("M-hyper", "M-hyper");
+        case: unknown_names_are_kept => ("Super_Shift-j", "Super_Shift-j");
+        case: no_modifiers => ("Return", "Return");
+        body: {
+            let aliases = [("super_shift", "M-S"), ("hyper", "H")];
+            assert_eq!(expand_modifier_aliases(pattern, &aliases), expected);
+        }
+    }
+
+    test_cases! {
+        key_code_matches;
+        args: (received_mask: KeyCodeMask, code: KeyCodeValue, expected: bool);
+
+        case: exact => (64, 44, true);
+        case: numlock => (64 | NUM_LOCK_MASK, 44, true);
+        case: capslock => (64 | CAPS_LOCK_MASK, 44, true);
+        case: both_locks => (64 | NUM_LOCK_MASK | CAPS_LOCK_MASK, 44, true);
+        case: missing_modifier => (NUM_LOCK_MASK, 44, false);
+        case: extra_modifier => (64 | 1, 44, false);
+        case: different_code => (64, 45, false);
+        body: {
+            let k = KeyCode { mask: 64, code: 44 };
+            assert_eq!(k.matches(received_mask, code), expected);
+        }
+    }
+
+    test_cases! {
+        explicit_lock_modifiers_are_preserved;
+        args: (received_mask: KeyCodeMask, expected: bool);
+
+        case: capslock => (64 | CAPS_LOCK_MASK, true);
+        case: capslock_and_numlock => (64 | CAPS_LOCK_MASK | NUM_LOCK_MASK, true);
+        case: without_capslock => (64, false);
+        case: numlock_only => (64 | NUM_LOCK_MASK, false);
+        body: {
+            let k = KeyCode { mask: 64 | CAPS_LOCK_MASK, code: 44 };
+            assert!(k.uses_lock_modifier());
+            assert_eq!(k.matches(received_mask, 44), expected);
+        }
+    }
+
+    #[test]
+    fn binding_candidates_are_most_specific_first() {
+        let k = KeyCode {
+            mask: 64 | NUM_LOCK_MASK | CAPS_LOCK_MASK,
+            code: 44,
+        };
+        let masks: Vec<KeyCodeMask> = k.binding_candidates().map(|c| c.mask()).collect();
+
+        assert_eq!(
+            masks,
+            vec![
+                64 | NUM_LOCK_MASK | CAPS_LOCK_MASK,
+                64 | CAPS_LOCK_MASK,
+                64 | NUM_LOCK_MASK,
+                64
+            ]
+        );
+        assert!(k.binding_candidates().all(|c| c.matches(k.mask(), 44)));
+    }
+
+    #[test]
+    fn received_key_presses_keep_lock_modifiers() {
+        let received = KeyCode {
+            mask: 64 | CAPS_LOCK_MASK | 1 << 8,
+            code: 44,
+        };
+
+        assert_eq!(
+            received.normalised_keeping_locks(),
+            KeyCode {
+                mask: 64 | CAPS_LOCK_MASK,
+                code: 44
+            }
+        );
+    }
+
+    #[test]
+    fn lock_mask_combinations_include_explicit_lock_modifiers() {
+        let masks = |mask| {
+            KeyCode { mask, code: 44 }
+                .lock_mask_combinations()
+                .collect::<Vec<_>>()
+        };
+
+        assert!(!KeyCode { mask: 64, code: 44 }.uses_lock_modifier());
+        assert_eq!(masks(64), LOCK_MASK_COMBINATIONS.to_vec());
+        assert_eq!(
+            masks(64 | CAPS_LOCK_MASK),
+            vec![CAPS_LOCK_MASK, NUM_LOCK_MASK | CAPS_LOCK_MASK]
+        );
+        assert_eq!(
+            masks(64 | CAPS_LOCK_MASK | NUM_LOCK_MASK),
+            vec![NUM_LOCK_MASK | CAPS_LOCK_MASK]
+        );
+    }
+
+    #[test]
+    fn empty_key_names_are_ignored() {
+        let names: Vec<String> = [
+            "1".into_key_names(),
+            "".into_key_names(),
+            " ".into_key_names(),
+            ("2"..="3").into_key_names(),
+        ]
+        .concat();
+
+        assert_eq!(names, vec!["1", "2", "3"]);
+    }
+
+    test_cases! {
+        suggest_key_name;
+        args: (name: &str, expected: Option<&str>);
+
+        case: transposed_letters => ("Retrun", Some("Return"));
+        case: wrong_case => ("return", Some("Return"));
+        case: missing_letter => ("Escpe", Some("Escape"));
+        case: exact_match => ("Return", None);
+        case: too_different => ("Delete", None);
+        case: short_names_need_to_be_close => ("j", None);
+        case: ties_are_alphabetical => ("Hom", Some("Home"));
+        body: {
+            let known = ["Return", "Escape", "Tab", "space", "k", "Home", "Homf", "Hop"];
+            assert_eq!(super::suggest_key_name(name, known.iter().copied()), expected);
+        }
+    }
+
+    test_cases! {
+        expand_modifier_groups;
+        args: (pattern: &str, expected: &[&str]);
+
+        case: no_groups => ("M-S-j", &["M-S-j"]);
+        case: key_only => ("j", &["j"]);
+        case: single_group => ("M-[S,C]-j", &["M-S-j", "M-C-j"]);
+        case: leading_group => ("[M,A]-j", &["M-j", "A-j"]);
+        case: whitespace_is_trimmed => ("M-[S, C]-j", &["M-S-j", "M-C-j"]);
+        case: empty_alternative => ("M-[,S]-j", &["M-j", "M-S-j"]);
+        case: multiple_groups => ("[M,A]-[S,C]-j", &["M-S-j", "M-C-j", "A-S-j", "A-C-j"]);
+        case: key_group => ("M-(Return|KP_Enter)", &["M-Return", "M-KP_Enter"]);
+        case: bare_key_group => ("(j|k)", &["j", "k"]);
+        case: key_group_whitespace_is_trimmed => ("M-( j | k )", &["M-j", "M-k"]);
+        case: key_and_modifier_groups => ("M-[,S]-(j|k)", &["M-j", "M-k", "M-S-j", "M-S-k"]);
+        case: unclosed_key_group => ("M-(j|k", &["M-(j|k"]);
+        body: {
+            assert_eq!(super::expand_modifier_groups(pattern), expected);
+        }
+    }
+
+    test_cases! {
+        key_code_normalised;
+        args: (mask: KeyCodeMask, expected: KeyCodeMask);
+
+        case: no_modifiers => (0, 0);
+        case: binding_modifiers_are_kept => (BINDING_MODIFIER_MASK, BINDING_MODIFIER_MASK);
+        case: capslock => (64 | CAPS_LOCK_MASK, 64);
+        case: numlock => (64 | NUM_LOCK_MASK, 64);
+        case: mod5 => (64 | 1 << 7, 64);
+        case: mouse_buttons => (64 | 1 << 8 | 1 << 10, 64);
+        body: {
+            let k = KeyCode { mask, code: 44 };
+            assert_eq!(k.normalised(), KeyCode { mask: expected, code: 44 });
+        }
+    }
+
+    test_cases! {
+        key_code_mask_diff;
+        args: (a: KeyCodeMask, b: KeyCodeMask, expected: KeyCodeMask);
+
+        case: same_mask => (64 | 1, 64 | 1, 0);
+        case: added_modifier => (64, 64 | 1, 1);
+        case: removed_modifier => (64 | 1, 64, 1);
+        case: swapped_modifiers => (64, 8, 64 | 8);
+        body: {
+            let k = KeyCode { mask: a, code: 44 };
+            assert_eq!(k.mask_diff(&k.with_mask(b)), expected);
+        }
+    }
+
+    test_cases! {
+        key_code_conflicts_with;
+        args: (a: KeyCode, b: KeyCode, expected: bool);
+
+        case: identical => (KeyCode { mask: 64, code: 44 }, KeyCode { mask: 64, code: 44 }, true);
+        case: lock_bits_are_ignored => (KeyCode { mask: 64 | CAPS_LOCK_MASK, code: 44 }, KeyCode { mask: 64 | NUM_LOCK_MASK, code: 44 }, true);
+        case: mouse_buttons_are_ignored => (KeyCode { mask: 64 | 1 << 8, code: 44 }, KeyCode { mask: 64, code: 44 }, true);
+        case: different_modifiers => (KeyCode { mask: 64, code: 44 }, KeyCode { mask: 64 | 1, code: 44 }, false);
+        case: different_keys => (KeyCode { mask: 64, code: 44 }, KeyCode { mask: 64, code: 45 }, false);
+        case: tap_and_bare_key => (KeyCode { mask: 0, code: 133 }.as_tap(), KeyCode { mask: 0, code: 133 }, true);
+        body: {
+            assert_eq!(a.conflicts_with(&b), expected);
+            assert_eq!(b.conflicts_with(&a), expected);
+        }
+    }
+
+    #[test]
+    fn with_mask_keeps_the_key_code() {
+        let k = KeyCode { mask: 64, code: 44 };
+        assert_eq!(k.with_mask(1), KeyCode { mask: 1, code: 44 });
+    }
+
+    #[test]
+    fn with_mask_drops_bits_that_can_not_be_bound() {
+        let k = KeyCode { mask: 64, code: 44 };
+        assert_eq!(k.with_mask(1 | CAPS_LOCK_MASK | 1 << 12).mask(), 1);
+    }
+
+    test_cases! {
+        key_code_new;
+        args: (mods: &[ModifierKey], expected: KeyCodeMask);
+
+        case: no_modifiers => (&[], 0);
+        case: single => (&[ModifierKey::Meta], 1 << 6);
+        case: multiple => (&[ModifierKey::Ctrl, ModifierKey::Alt, ModifierKey::Shift], 1 << 2 | 1 << 3 | 1);
+        case: repeated => (&[ModifierKey::Hyper, ModifierKey::Hyper], 1 << 5);
+        body: {
+            let k = KeyCode::new(mods, 44);
+            assert_eq!(k, KeyCode { mask: expected, code: 44 });
+            assert_eq!(k.normalised(), k);
+        }
+    }
+
+    #[test]
+    fn normalised_key_codes_share_a_map_key() {
+        let bound = KeyCode { mask: 64, code: 44 };
+        let received = KeyCode {
+            mask: 64 | CAPS_LOCK_MASK,
+            code: 44,
+        };
+        let bindings: HashMap<KeyCode, &str> = map! { bound.normalised() => "bound", };
+
+        assert_ne!(bound, received);
+        assert_eq!(bindings.get(&received.normalised()), Some(&"bound"));
+    }
+
+    #[test]
+    fn initial_key_presses_are_not_repeats() {
+        let mut t = KeyRepeatTracker::default();
+        assert!(!t.press(44, 1));
+        t.release(44, 2);
+        assert!(!t.press(44, 3));
+        assert!(!t.press(45, 4));
+    }
+
+    #[test]
+    fn autorepeat_release_press_pairs_are_repeats() {
+        let mut t = KeyRepeatTracker::default();
+        assert!(!t.press(44, 1));
+        t.release(44, 10);
+        assert!(t.press(44, 10));
+        t.release(44, 20);
+        assert!(t.press(44, 20));
+    }
+
+    #[test]
+    fn presses_of_held_keys_are_repeats() {
+        let mut t = KeyRepeatTracker::default();
+        assert!(!t.press(44, 1));
+        assert!(t.press(44, 10));
+        assert!(t.press(44, 20));
+    }
+
+    test_cases! {
+        modifier_from_char;
+        args: (c: char, expected: Option<ModifierKey>);
+
+        case: ctrl => ('C', Some(ModifierKey::Ctrl));
+        case: alt => ('A', Some(ModifierKey::Alt));
+        case: shift => ('S', Some(ModifierKey::Shift));
+        case: meta => ('M', Some(ModifierKey::Meta));
+        case: hyper => ('H', Some(ModifierKey::Hyper));
+        case: lowercase_is_invalid => ('m', None);
+        case: unknown => ('Q', None);
+        body: {
+            assert_eq!(ModifierKey::from_char(c), expected);
+        }
+    }
+
+    test_cases! {
+        parse_mouse_binding;
+        args: (pattern: &str, expected: std::result::Result<MouseState, KeyBindingError>);
+
+        case: no_modifiers => ("Button1", Ok(MouseState::new(MouseButton::Left, vec![])));
+        case: single_modifier => (
+            "M-Button3",
+            Ok(MouseState::new(MouseButton::Right, vec![ModifierKey::Meta]))
+        );
+        case: modifier_order_is_ignored => (
+            "S-Super-Button4",
+            Ok(MouseState::new(MouseButton::ScrollUp, vec![ModifierKey::Meta, ModifierKey::Shift]))
+        );
+        case: unknown_button => (
+            "M-Button9",
+            Err(KeyBindingError::UnknownMouseButton("Button9".into()))
+        );
+        case: unknown_modifier => (
+            "Q-Button1",
+            Err(KeyBindingError::UnknownModifier("Q".into()))
+        );
+        body: {
+            assert_eq!(parse_mouse_binding(pattern), expected);
+        }
+    }
+}
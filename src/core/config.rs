@@ -94,6 +94,15 @@ __with_builder_and_getters! {
     Concrete top_bar: bool; => true;
     /// the height of the space to be reserved for a status bar in pixels
     Concrete bar_height: u32; => 18;
+    /// how long to wait (in milliseconds) for the next key press of a
+    /// [KeyChord][crate::core::bindings::KeyChord] before it is abandoned.
+    ///
+    /// Chords are not yet dispatched by the [WindowManager][crate::core::manager::WindowManager]
+    /// so this currently has no effect: it is reserved for when they are.
+    Concrete chord_timeout_ms: u64; => 1000;
+    /// the longest (in milliseconds) that a modifier key can be held for and still count as a
+    /// tap for `tap:` key bindings. This should be shorter than your keyboard autorepeat delay.
+    Concrete tap_timeout_ms: u64; => 200;
 }
 
 impl Config {
@@ -4,41 +4,485 @@ use crate::{
     ErrorHandler, PenroseError, Result,
 };
 
+use nix::{
+    sys::signal::{kill, Signal},
+    unistd::{getuid, setsid, Pid},
+};
+
 use std::{
-    io::Read,
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
+    env,
+    ffi::OsStr,
+    fmt, fs,
+    io::{self, ErrorKind, Read},
+    os::unix::{
+        fs::{MetadataExt, PermissionsExt},
+        process::CommandExt,
+    },
+    panic,
+    path::{Path, PathBuf},
     process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Mutex, Once,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
+pub use penrose_binding_helpers::{expand_template, TemplateError};
+
+// How long respawn waits for existing instances of a program to exit after sending SIGTERM
+const RESPAWN_TIMEOUT: Duration = Duration::from_millis(500);
+
+// Programs that respawn will not stop running instances of as they are almost always shells
+// unrelated to the command being respawned
+const SHELLS: [&str; 4] = ["sh", "bash", "zsh", "dash"];
+
+// The most recently parsed output of `xmodmap -pke` (if any)
+static KEYCODE_CACHE: Mutex<Option<CodeMap>> = Mutex::new(None);
+
+// User provided teardown to run if penrose panics (see set_panic_cleanup)
+static PANIC_CLEANUP: Mutex<Option<Box<dyn Fn() + Send>>> = Mutex::new(None);
+static PANIC_HOOK: Once = Once::new();
+
+// When set, spawned commands are logged rather than run (see set_spawn_dry_run)
+static SPAWN_DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Log commands instead of running them when they are spawned.
+///
+/// While this is enabled, [spawn()] and its variants log the command they would have run (split
+/// into its program and arguments) at info level and return `Ok` without starting a process.
+/// This is intended for testing a new config: you can check that each of your key bindings maps
+/// to the right command without opening a window for every one of them. [spawn_pid] returns
+/// `None` as there is no child process and [respawn] does not stop any running instances of the
+/// program.
+///
+/// Functions that return the output of a command, such as [spawn_for_output], still run it as
+/// their callers depend on that output. Dry runs are disabled by default.
+pub fn set_spawn_dry_run(enabled: bool) {
+    SPAWN_DRY_RUN.store(enabled, Ordering::SeqCst);
+}
+
+/// Whether spawned commands are currently being logged instead of run (see [set_spawn_dry_run]).
+pub fn spawn_dry_run() -> bool {
+    SPAWN_DRY_RUN.load(Ordering::SeqCst)
+}
+
+// Log the command that would have been run if this is a dry run
+fn skip_for_dry_run<A: AsRef<OsStr>>(program: &str, args: &[A]) -> bool {
+    if spawn_dry_run() {
+        let args: Vec<&OsStr> = args.iter().map(|a| a.as_ref()).collect();
+        info!(?program, ?args, "dry run: not spawning command");
+    }
+
+    spawn_dry_run()
+}
+
 /// Run an external command
 ///
 /// This redirects the process stdout and stderr to /dev/null.
+///
+/// The command string is split into arguments on whitespace with basic shell style quoting
+/// supported: single or double quoted segments and backslash escaped spaces are kept together as
+/// a single argument, i.e. `notify-send "hello world"` is run with a single argument.
 pub fn spawn<S: Into<String>>(cmd: S) -> Result<()> {
-    let s = cmd.into();
-    let parts: Vec<&str> = s.split_whitespace().collect();
-    let result = if parts.len() > 1 {
-        Command::new(parts[0])
-            .args(&parts[1..])
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
-    } else {
-        Command::new(parts[0])
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
+    spawn_child(cmd.into()).map(|_| ())
+}
+
+/// Run an external command and return the process ID of the spawned child
+///
+/// This behaves exactly like [spawn()] but returns the PID of the child process on success so
+/// that it can be tracked later on, e.g. to match it against the `_NET_WM_PID` of a new window.
+/// If the command can not be run then a warning is logged and `None` is returned. `None` is also
+/// returned for dry runs (see [set_spawn_dry_run]) as no process is started.
+pub fn spawn_pid<S: Into<String>>(cmd: S) -> Option<u32> {
+    match spawn_child(cmd.into()) {
+        Ok(pid) => pid,
+        Err(e) => {
+            warn!(%e, "unable to spawn command");
+            None
+        }
+    }
+}
+
+// Run cmd with its stdout and stderr redirected to /dev/null, returning the PID of the child
+// (or None if this is a dry run and nothing was spawned)
+fn spawn_child(cmd: String) -> Result<Option<u32>> {
+    let parts = command_parts(&cmd)?;
+    if skip_for_dry_run(&parts[0], &parts[1..]) {
+        return Ok(None);
+    }
+
+    let result = Command::new(&parts[0])
+        .args(&parts[1..])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+
+    match result {
+        Ok(child) => Ok(Some(child.id())),
+        Err(e) => Err(spawn_error(&cmd, &parts[0], e)),
+    }
+}
+
+/// Run an external command after expanding any environment variables it references
+///
+/// `$VAR` and `${VAR}` in `cmd` are replaced with the value of `VAR` from the environment of the
+/// penrose process before `cmd` is split into arguments and run using [spawn()]. Variables that
+/// are not set are replaced with an empty string and a warning is logged. A `$` that is not
+/// followed by a variable name is left as-is.
+///
+/// ```no_run
+/// # use penrose::core::helpers::spawn_expand;
+/// # fn example() -> penrose::Result<()> {
+/// spawn_expand("feh --bg-fill ${XDG_CONFIG_HOME}/wallpaper.png")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [spawn()] itself does not expand variables so use that if your command needs a literal `$`.
+pub fn spawn_expand<S: Into<String>>(cmd: S) -> Result<()> {
+    spawn(expand_env_vars(&cmd.into(), |name| env::var(name).ok()))
+}
+
+// Replace $VAR and ${VAR} in s using the given lookup, with unknown variables becoming empty
+fn expand_env_vars(s: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut expanded = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(ix) = rest.find('$') {
+        expanded.push_str(&rest[..ix]);
+        let after = &rest[ix + 1..];
+
+        let (name, remaining) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) if end > 0 && braced[..end].chars().all(is_name_char) => {
+                    (&braced[..end], &braced[end + 1..])
+                }
+                _ => ("", after),
+            },
+            None => {
+                let end = after.find(|c| !is_name_char(c)).unwrap_or(after.len());
+                (&after[..end], &after[end..])
+            }
+        };
+
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+
+        match lookup(name) {
+            Some(value) => expanded.push_str(&value),
+            None => warn!(?name, "unknown environment variable in spawn command"),
+        }
+        rest = remaining;
+    }
+
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Run an external command, first stopping any running instances of the same program.
+///
+/// Running processes owned by the current user whose program name matches the basename of the
+/// program in `cmd` (split from its arguments in the same way as [spawn()]) are sent `SIGTERM`
+/// and given a short time to exit before `cmd` is run using [spawn()]. This is intended for
+/// things like status bars and compositors that are started from your config so that restarting
+/// penrose does not leave duplicate instances running.
+///
+/// If `cmd` is run using a shell (`sh -c "..."`, `bash`, `zsh` or `dash`) then matching on the
+/// program name would stop every running shell, so a warning is logged and `cmd` is spawned
+/// without stopping anything.
+///
+/// ```no_run
+/// # use penrose::core::helpers::respawn;
+/// # fn example() -> penrose::Result<()> {
+/// respawn("polybar --reload main")?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn respawn<S: Into<String>>(cmd: S) -> Result<()> {
+    let cmd = cmd.into();
+    let name = command_parts(&cmd).map(|parts| program_basename(&parts[0]))?;
+    if spawn_dry_run() {
+        return spawn(cmd);
+    }
+
+    if SHELLS.contains(&name.as_str()) {
+        warn!(?name, %cmd, "not stopping running shells before respawning");
+        return spawn(cmd);
+    }
+
+    let pids = pids_for_program(&name);
+
+    if !pids.is_empty() {
+        debug!(?name, ?pids, "stopping running instances before respawning");
+        terminate(&pids);
+    }
+
+    spawn(cmd)
+}
+
+// The name of a program as it appears in the process table: '/usr/bin/polybar' -> 'polybar'
+fn program_basename(program: &str) -> String {
+    Path::new(program)
+        .file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| program.to_string())
+}
+
+// The PIDs of all running processes owned by the current user (other than penrose itself) where
+// the basename of argv[0] matches the given program name
+fn pids_for_program(name: &str) -> Vec<i32> {
+    let own_pid = std::process::id() as i32;
+    let uid = getuid().as_raw();
+    let entries = match fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!(%e, "unable to read the process table");
+            return vec![];
+        }
     };
 
+    entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<i32>().ok())
+        .filter(|&pid| pid != own_pid)
+        .filter(|&pid| process_uid(pid) == Some(uid))
+        .filter(|&pid| process_name(pid).as_deref() == Some(name))
+        .collect()
+}
+
+// Exited processes that have not yet been reaped have an empty command line so they are treated
+// as not running.
+fn process_name(pid: i32) -> Option<String> {
+    let cmdline = fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    let argv0 = cmdline
+        .split(|&b| b == 0)
+        .next()
+        .filter(|s| !s.is_empty())?;
+
+    Some(program_basename(&String::from_utf8_lossy(argv0)))
+}
+
+// The owner of a process is the owner of its /proc entry
+fn process_uid(pid: i32) -> Option<u32> {
+    fs::metadata(format!("/proc/{}", pid)).ok().map(|m| m.uid())
+}
+
+// Send SIGTERM to each of the given processes and wait (up to RESPAWN_TIMEOUT) for them to exit
+fn terminate(pids: &[i32]) {
+    for &pid in pids {
+        if let Err(e) = kill(Pid::from_raw(pid), Signal::SIGTERM) {
+            warn!(%e, pid, "unable to send SIGTERM");
+        }
+    }
+
+    let start = Instant::now();
+    while pids.iter().any(|&pid| process_name(pid).is_some()) {
+        if start.elapsed() > RESPAWN_TIMEOUT {
+            warn!(?pids, "processes still running after SIGTERM");
+            return;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Run an external command with its stdout and stderr inherited from penrose
+///
+/// The command is split into arguments in exactly the same way as [spawn()] but its output is
+/// written to wherever penrose's own output is going (e.g. the terminal or log file penrose was
+/// started from) rather than /dev/null. This is intended for debugging misbehaving external
+/// commands: switch back to [spawn()] once the problem has been found.
+pub fn spawn_inherit_io<S: Into<String>>(cmd: S) -> Result<()> {
+    let cmd = cmd.into();
+    let parts = command_parts(&cmd)?;
+    if skip_for_dry_run(&parts[0], &parts[1..]) {
+        return Ok(());
+    }
+
+    let result = Command::new(&parts[0])
+        .args(&parts[1..])
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .spawn();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => Err(spawn_error(&cmd, &parts[0], e)),
+    }
+}
+
+/// Run an external command in a new session so that it is not tied to the lifetime of penrose
+///
+/// This behaves like [spawn()] but the child calls `setsid` before running `cmd`, detaching it
+/// from the session and process group of the window manager. Use this for long running programs
+/// (such as a browser launched at startup) that should survive penrose exiting or restarting.
+pub fn spawn_detached<S: Into<String>>(cmd: S) -> Result<()> {
+    let cmd = cmd.into();
+    let parts = command_parts(&cmd)?;
+    if skip_for_dry_run(&parts[0], &parts[1..]) {
+        return Ok(());
+    }
+
+    let mut command = Command::new(&parts[0]);
+    command
+        .args(&parts[1..])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    // SAFETY: setsid is async-signal-safe and no memory is allocated between fork and exec
+    unsafe {
+        command.pre_exec(|| {
+            setsid().map_err(|_| io::Error::last_os_error())?;
+            Ok(())
+        });
+    }
+
+    match command.spawn() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(spawn_error(&cmd, &parts[0], e)),
+    }
+}
+
+/// Run an external command using the user's shell
+///
+/// Unlike [spawn()], `cmd` is not split into arguments: it is passed as-is to `$SHELL -c` (or
+/// `sh -c` if `$SHELL` is not set) so shell features such as pipes, redirects, globs and
+/// variable expansion all work. This redirects the shell's stdout and stderr to /dev/null.
+///
+/// ```no_run
+/// # use penrose::core::helpers::spawn_shell;
+/// # fn example() -> penrose::Result<()> {
+/// spawn_shell("maim -s | xclip -selection clipboard -t image/png")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Prefer [spawn()] or [spawn_with_args] for simple commands, particularly when the command
+/// includes user provided data that should not be interpreted by the shell. If you do need to
+/// include such data in a shell command then quote it using [shell_quote].
+pub fn spawn_shell<S: Into<String>>(cmd: S) -> Result<()> {
+    let cmd = cmd.into();
+    if cmd.trim().is_empty() {
+        warn!(?cmd, "attempt to spawn an empty command");
+        return Err(PenroseError::SpawnFailed(
+            cmd,
+            "no command was given".into(),
+        ));
+    }
+
+    let shell = env::var("SHELL")
+        .ok()
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "sh".into());
+    if skip_for_dry_run(&shell, &["-c", &cmd]) {
+        return Ok(());
+    }
+
+    let result = Command::new(&shell)
+        .arg("-c")
+        .arg(&cmd)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+
     match result {
         Ok(_) => Ok(()),
-        Err(e) => Err(e.into()),
+        Err(e) => Err(spawn_error(&cmd, &shell, e)),
+    }
+}
+
+/// Quote a string so that it is passed as a single literal argument when used in a command run
+/// by [spawn_shell].
+///
+/// The result is wrapped in single quotes with any single quotes in `s` escaped, so no part of it
+/// is interpreted by the shell (variables, globs, `;` and so on are all left as-is):
+///
+/// ```
+/// # use penrose::core::helpers::shell_quote;
+/// let title = "it's; rm -rf ~";
+/// assert_eq!(shell_quote(title), r"'it'\''s; rm -rf ~'");
+/// ```
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Run an external command with additional environment variables set
+///
+/// This redirects the process stdout and stderr to /dev/null and splits `cmd` into arguments in
+/// the same way as [spawn()]. Each `(key, value)` pair in `env` is set for the child process,
+/// with an empty value removing that variable from the environment the child inherits:
+///
+/// ```no_run
+/// # use penrose::core::helpers::spawn_with_env;
+/// # fn example() -> penrose::Result<()> {
+/// spawn_with_env(
+///     "flameshot gui",
+///     vec![("QT_QPA_PLATFORM".into(), "xcb".into()), ("WAYLAND_DISPLAY".into(), "".into())],
+/// )?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn spawn_with_env<S: Into<String>>(cmd: S, env: Vec<(String, String)>) -> Result<()> {
+    let cmd = cmd.into();
+    let parts = command_parts(&cmd)?;
+    if skip_for_dry_run(&parts[0], &parts[1..]) {
+        return Ok(());
+    }
+
+    let mut command = Command::new(&parts[0]);
+    command
+        .args(&parts[1..])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    for (key, val) in env.iter() {
+        if val.is_empty() {
+            command.env_remove(key);
+        } else {
+            command.env(key, val);
+        }
+    }
+
+    match command.spawn() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(spawn_error(&cmd, &parts[0], e)),
     }
 }
 
 /// Run an external command with the specified command line arguments
 ///
 /// This redirects the process stdout and stderr to /dev/null.
-pub fn spawn_with_args<S: Into<String>>(cmd: S, args: &[&str]) -> Result<()> {
-    let result = Command::new(cmd.into())
+///
+/// Unlike [spawn()], `args` are passed through to the process exactly as given with no
+/// splitting or quote handling, making this the safe choice for arguments built from user data
+/// such as file paths containing spaces. Any slice of string types can be used:
+///
+/// ```no_run
+/// # use penrose::core::helpers::spawn_with_args;
+/// # fn example() -> penrose::Result<()> {
+/// let path = String::from("/home/me/My Documents/notes.txt");
+/// spawn_with_args("xdg-open", &[path])?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn spawn_with_args<S, A>(cmd: S, args: &[A]) -> Result<()>
+where
+    S: Into<String>,
+    A: AsRef<OsStr>,
+{
+    let cmd = cmd.into();
+    if skip_for_dry_run(&cmd, args) {
+        return Ok(());
+    }
+
+    let result = Command::new(&cmd)
         .args(args)
         .stdout(Stdio::null())
         .stderr(Stdio::null())
@@ -46,29 +490,32 @@ pub fn spawn_with_args<S: Into<String>>(cmd: S, args: &[&str]) -> Result<()> {
 
     match result {
         Ok(_) => Ok(()),
-        Err(e) => Err(e.into()),
+        Err(e) => Err(spawn_error(&command_line(&cmd, args), &cmd, e)),
     }
 }
 
 /// Run an external command and return its output.
 ///
+/// The command string is split into arguments in the same way as [spawn()]. This blocks until the
+/// child process closes its stdout and any failure to spawn the process or read its output is
+/// returned as an error. The output is returned as-is: use the [spawn_for_output][1] macro if you
+/// want it trimmed and split into lines.
+///
 /// NOTE: std::process::Command::output will not work within penrose due to the
 /// way that signal handling is set up. Use this function if you need to access the
 /// output of a process that you spawn.
+///
+/// [1]: crate::spawn_for_output
 pub fn spawn_for_output<S: Into<String>>(cmd: S) -> Result<String> {
     let cmd = cmd.into();
     info!(?cmd, "spawning subprocess for output");
-    let parts: Vec<&str> = cmd.split_whitespace().collect();
-    let result = if parts.len() > 1 {
-        Command::new(parts[0])
-            .stdout(Stdio::piped())
-            .args(&parts[1..])
-            .spawn()
-    } else {
-        Command::new(parts[0]).stdout(Stdio::piped()).spawn()
-    };
+    let parts = command_parts(&cmd)?;
+    let child = Command::new(&parts[0])
+        .stdout(Stdio::piped())
+        .args(&parts[1..])
+        .spawn()
+        .map_err(|e| spawn_error(&cmd, &parts[0], e))?;
 
-    let child = result?;
     let mut buff = String::new();
     Ok(child
         .stdout
@@ -77,19 +524,69 @@ pub fn spawn_for_output<S: Into<String>>(cmd: S) -> Result<String> {
         .map(|_| buff)?)
 }
 
+/// Run an external command and return its output, killing it if it takes longer than `timeout`.
+///
+/// This behaves like [spawn_for_output] but only waits up to `timeout` for the child to close its
+/// stdout. If the deadline passes the child is killed and [PenroseError::SpawnTimeout] is
+/// returned, so a helper command that hangs can not block penrose or be left running.
+///
+/// ```no_run
+/// # use penrose::core::helpers::spawn_for_output_timeout;
+/// # use std::time::Duration;
+/// # fn example() -> penrose::Result<()> {
+/// let volume = spawn_for_output_timeout("pamixer --get-volume", Duration::from_millis(500))?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn spawn_for_output_timeout<S: Into<String>>(cmd: S, timeout: Duration) -> Result<String> {
+    let cmd = cmd.into();
+    info!(?cmd, ?timeout, "spawning subprocess for output");
+    let parts = command_parts(&cmd)?;
+    let mut child = Command::new(&parts[0])
+        .stdout(Stdio::piped())
+        .args(&parts[1..])
+        .spawn()
+        .map_err(|e| spawn_error(&cmd, &parts[0], e))?;
+
+    let mut stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| PenroseError::SpawnProc(cmd.clone()))?;
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buff = String::new();
+        let _ = tx.send(stdout.read_to_string(&mut buff).map(|_| buff));
+    });
+
+    match rx.recv_timeout(timeout) {
+        Ok(res) => Ok(res?),
+        Err(_) => {
+            warn!(?cmd, ?timeout, "subprocess timed out: killing it");
+            let _ = child.kill();
+            let _ = child.wait();
+            Err(PenroseError::SpawnTimeout(cmd, timeout))
+        }
+    }
+}
+
 /// Run an external command with arguments and return its output.
 ///
 /// NOTE: std::process::Command::output will not work within penrose due to the
 /// way that signal handling is set up. Use this function if you need to access the
 /// output of a process that you spawn.
-pub fn spawn_for_output_with_args<S: Into<String>>(cmd: S, args: &[&str]) -> Result<String> {
+pub fn spawn_for_output_with_args<S, A>(cmd: S, args: &[A]) -> Result<String>
+where
+    S: Into<String>,
+    A: AsRef<OsStr> + fmt::Debug,
+{
     let cmd = cmd.into();
 
     info!(?cmd, ?args, "spawning subprocess for output");
     let child = Command::new(&cmd)
         .stdout(Stdio::piped())
         .args(args)
-        .spawn()?;
+        .spawn()
+        .map_err(|e| spawn_error(&command_line(&cmd, args), &cmd, e))?;
 
     info!(?cmd, ?args, "reading output");
     let mut buff = String::new();
@@ -100,6 +597,246 @@ pub fn spawn_for_output_with_args<S: Into<String>>(cmd: S, args: &[&str]) -> Res
         .map(|_| buff)?)
 }
 
+/// Find the executable that would be run by [spawn()] for the given command string.
+///
+/// The program name is taken from `cmd` using the same argument splitting as [spawn()] and then
+/// searched for in each directory of `$PATH` in turn. Program names containing a `/` are checked
+/// directly instead. This can be used at startup to check that the programs used in your key
+/// bindings are installed:
+///
+/// ```no_run
+/// # use penrose::core::helpers::resolve_executable;
+/// for cmd in &["dmenu_run", "alacritty --class scratchpad"] {
+///     if resolve_executable(cmd).is_none() {
+///         eprintln!("'{}' was not found on your PATH", cmd);
+///     }
+/// }
+/// ```
+pub fn resolve_executable(cmd: &str) -> Option<PathBuf> {
+    let program = split_args(cmd).into_iter().next()?;
+    if program.contains('/') {
+        let path = PathBuf::from(program);
+        return if is_executable(&path) {
+            Some(path)
+        } else {
+            None
+        };
+    }
+
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(&program))
+        .find(|path| is_executable(path))
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+// Include the full command line in errors from spawning a process so that it is clear which
+// command failed, pointing the user at their PATH if the program itself could not be found.
+fn spawn_error(cmd: &str, program: &str, e: std::io::Error) -> PenroseError {
+    let reason = match e.kind() {
+        ErrorKind::NotFound => format!(
+            "'{}' was not found: is it installed and on your PATH?",
+            program
+        ),
+        _ => e.to_string(),
+    };
+
+    PenroseError::SpawnFailed(cmd.into(), reason)
+}
+
+fn command_line<A: AsRef<OsStr>>(cmd: &str, args: &[A]) -> String {
+    args.iter().fold(cmd.to_string(), |s, a| {
+        format!("{} {}", s, a.as_ref().to_string_lossy())
+    })
+}
+
+// Split a command string into the program and its arguments, returning an error rather than an
+// empty list if there is no program to run.
+fn command_parts(cmd: &str) -> Result<Vec<String>> {
+    let parts = split_args(cmd);
+    if parts.is_empty() {
+        warn!(?cmd, "attempt to spawn an empty command");
+        return Err(PenroseError::SpawnFailed(
+            cmd.into(),
+            "no command was given".into(),
+        ));
+    }
+
+    Ok(parts)
+}
+
+// Split a command string into its arguments, keeping quoted segments and escaped whitespace
+// together. Quotes are removed from the resulting arguments.
+fn split_args(s: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote: Option<char> = None;
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+                in_arg = true;
+            }
+            (Some(_), c) => current.push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+
+    if in_arg {
+        args.push(current);
+    }
+
+    args
+}
+
+/// The ways in which reading the system keymap (using xmodmap or another [KeymapSource]) can fail
+#[derive(thiserror::Error, Debug)]
+pub enum XmodmapError {
+    /// The keymap command could not be run
+    #[error("unable to fetch keycodes: {0}")]
+    Spawn(#[from] std::io::Error),
+
+    /// The keymap command ran but exited with an error
+    #[error("keymap command exited with an error: {0}")]
+    Failed(String),
+
+    /// The output of the keymap command was not valid utf8
+    #[error("invalid utf8 from keymap command: {0}")]
+    NonUtf8(#[from] std::string::FromUtf8Error),
+
+    /// An unknown keymap source was requested via PENROSE_KEYMAP_SOURCE
+    #[error("unknown keymap source '{0}': expected one of 'xmodmap' or 'xkbcomp'")]
+    UnknownSource(String),
+}
+
+/// The environment variable used by [keymap_source_from_env] to select a [KeymapSource]
+pub const KEYMAP_SOURCE_VAR: &str = "PENROSE_KEYMAP_SOURCE";
+
+/// Something that is able to read the current keyboard mapping of the X server as a map of key
+/// names to key codes.
+pub trait KeymapSource {
+    /// Read the current keymap
+    fn code_map(&self) -> std::result::Result<CodeMap, XmodmapError>;
+
+    /// Read the current keymap grouped into the key names for each key code, in ascending order
+    /// of key code (see [parse_code_and_names]).
+    ///
+    /// By default the names for each key code are taken from [KeymapSource::code_map] and
+    /// sorted alphabetically.
+    fn code_and_names(&self) -> std::result::Result<Vec<(u8, Vec<String>)>, XmodmapError> {
+        let mut grouped: BTreeMap<u8, Vec<String>> = BTreeMap::new();
+        for (name, code) in self.code_map()? {
+            grouped.entry(code).or_default().push(name);
+        }
+
+        Ok(grouped
+            .into_iter()
+            .map(|(code, mut names)| {
+                names.sort();
+                (code, names)
+            })
+            .collect())
+    }
+}
+
+/// Read the keymap by running `xmodmap -pke` (see [try_keycodes_from_xmodmap])
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Xmodmap;
+
+impl KeymapSource for Xmodmap {
+    fn code_map(&self) -> std::result::Result<CodeMap, XmodmapError> {
+        try_keycodes_from_xmodmap()
+    }
+
+    fn code_and_names(&self) -> std::result::Result<Vec<(u8, Vec<String>)>, XmodmapError> {
+        Ok(parse_code_and_names(&xmodmap_output()?))
+    }
+}
+
+/// Read the keymap by dumping the compiled xkb keymap of the X server using `xkbcomp`.
+///
+/// This picks up keys that are configured through xkb (e.g. via `setxkbmap` options) but which
+/// can be missing from the output of `xmodmap -pke`. See [parse_xkb_keymap] for how the output
+/// is read.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Xkbcomp;
+
+impl KeymapSource for Xkbcomp {
+    fn code_map(&self) -> std::result::Result<CodeMap, XmodmapError> {
+        let display = env::var("DISPLAY").unwrap_or_else(|_| ":0".into());
+        let output = Command::new("xkbcomp")
+            .args(["-xkb", &display, "-"])
+            .output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(XmodmapError::Failed(stderr.trim().to_string()));
+        }
+
+        Ok(parse_xkb_keymap(&String::from_utf8(output.stdout)?))
+    }
+}
+
+/// Select a [KeymapSource] using the `PENROSE_KEYMAP_SOURCE` environment variable.
+///
+/// Accepted values are `xmodmap` (the default if the variable is not set) and `xkbcomp`.
+pub fn keymap_source_from_env() -> std::result::Result<Box<dyn KeymapSource>, XmodmapError> {
+    match env::var(KEYMAP_SOURCE_VAR) {
+        Err(_) => Ok(Box::new(Xmodmap)),
+        Ok(s) => match s.trim() {
+            "" | "xmodmap" => Ok(Box::new(Xmodmap)),
+            "xkbcomp" | "xkb" => Ok(Box::new(Xkbcomp)),
+            _ => Err(XmodmapError::UnknownSource(s)),
+        },
+    }
+}
+
+/// Read the system keymap using the [KeymapSource] selected by [keymap_source_from_env].
+///
+/// This is what [gen_keybindings][crate::gen_keybindings] uses to resolve key names, so setting
+/// `PENROSE_KEYMAP_SOURCE=xkbcomp` when running penrose switches it over to reading the xkb
+/// keymap instead of using xmodmap. Every other helper that reads the keymap at runtime (such as
+/// [keycodes_from_xmodmap_cached], [known_key_names] and [code_and_names]) uses the same source
+/// so that they always agree with the key codes that were bound at startup.
+///
+/// # Panics
+/// This function will panic if the keymap source is unknown or fails to read the keymap. See
+/// [try_keycodes_from_keymap] for a non-panicking version.
+pub fn keycodes_from_keymap() -> CodeMap {
+    match try_keycodes_from_keymap() {
+        Ok(codes) => codes,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Read the system keymap using the [KeymapSource] selected by [keymap_source_from_env],
+/// returning an error if the source is unknown or fails to read the keymap.
+pub fn try_keycodes_from_keymap() -> std::result::Result<CodeMap, XmodmapError> {
+    keymap_source_from_env()?.code_map()
+}
+
 /// Run the xmodmap command to dump the system keymap table.
 ///
 /// This is done in a form that we can load in and convert back to key
@@ -109,28 +846,412 @@ pub fn spawn_for_output_with_args<S: Into<String>>(cmd: S, args: &[&str]) -> Res
 ///
 /// # Panics
 /// This function will panic if it is unable to fetch keycodes using the xmodmap
-/// binary on your system or if the output of `xmodmap -pke` is not valid
+/// binary on your system. See [try_keycodes_from_xmodmap] for a non-panicking version.
 pub fn keycodes_from_xmodmap() -> CodeMap {
-    match Command::new("xmodmap").arg("-pke").output() {
-        Err(e) => panic!("unable to fetch keycodes via xmodmap: {}", e),
-        Ok(o) => match String::from_utf8(o.stdout) {
-            Err(e) => panic!("invalid utf8 from xmodmap: {}", e),
-            Ok(s) => s
-                .lines()
-                .flat_map(|l| {
-                    let mut words = l.split_whitespace(); // keycode <code> = <names ...>
-                    let key_code: u8 = match words.nth(1) {
-                        Some(word) => match word.parse() {
-                            Ok(val) => val,
-                            Err(e) => panic!("{}", e),
-                        },
-                        None => panic!("unexpected output format from xmodmap -pke"),
-                    };
-                    words.skip(1).map(move |name| (name.into(), key_code))
-                })
-                .collect::<CodeMap>(),
-        },
+    match try_keycodes_from_xmodmap() {
+        Ok(codes) => codes,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Run the xmodmap command to dump the system keymap table, returning an error if xmodmap could
+/// not be run successfully.
+///
+/// Lines of output that do not match the expected `keycode <code> = <names ...>` format are
+/// skipped.
+pub fn try_keycodes_from_xmodmap() -> std::result::Result<CodeMap, XmodmapError> {
+    Ok(parse_xmodmap_output(&xmodmap_output()?))
+}
+
+// The raw output of 'xmodmap -pke', run using the C locale so that the output format does not
+// depend on the locale of the user
+pub(crate) fn xmodmap_output() -> std::result::Result<String, XmodmapError> {
+    let output = Command::new("xmodmap")
+        .arg("-pke")
+        .env("LC_ALL", "C")
+        .env("LANG", "C")
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(XmodmapError::Failed(stderr.trim().to_string()));
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Group the key names in the current keymap by key code, reading the keymap using the
+/// [KeymapSource] selected by [keymap_source_from_env].
+///
+/// See [parse_code_and_names] for details of how the output of xmodmap is grouped.
+///
+/// # Panics
+/// This function will panic under the same conditions as [keycodes_from_keymap].
+pub fn code_and_names() -> Vec<(u8, Vec<String>)> {
+    match keymap_source_from_env().and_then(|source| source.code_and_names()) {
+        Ok(grouped) => grouped,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Parse the output of `xmodmap -pke` into the key names listed for each key code.
+///
+/// Lines are parsed in the same way as [parse_xmodmap_output] but the results are grouped by key
+/// code (in ascending order) rather than by name, matching the `keycode 38 = a A` layout of the
+/// xmodmap output. Key codes with no names are skipped and each name is listed once for a given
+/// key code, in order of the lowest shift level it appears at.
+///
+/// ```
+/// # use penrose::core::helpers::parse_code_and_names;
+/// let output = "keycode  38 = a A a A\nkeycode   8 =\nkeycode  10 = 1 exclam 1 exclam";
+///
+/// assert_eq!(
+///     parse_code_and_names(output),
+///     vec![
+///         (10, vec!["1".to_string(), "exclam".to_string()]),
+///         (38, vec!["a".to_string(), "A".to_string()]),
+///     ]
+/// );
+/// ```
+pub fn parse_code_and_names(input: &str) -> Vec<(u8, Vec<String>)> {
+    let mut grouped: BTreeMap<u8, Vec<String>> = BTreeMap::new();
+    for (name, code) in input.lines().flat_map(keycodes_from_xmodmap_line) {
+        let names = grouped.entry(code).or_default();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    grouped.into_iter().collect()
+}
+
+/// The names of all keys in the current keymap, sorted and de-duplicated.
+///
+/// These are the key names that can be used in bindings on this machine, read using
+/// [keycodes_from_keymap] so that the two always agree.
+///
+/// # Panics
+/// This function will panic under the same conditions as [keycodes_from_keymap].
+pub fn known_key_names() -> Vec<String> {
+    sorted_key_names(&keycodes_from_keymap())
+}
+
+fn sorted_key_names(codes: &CodeMap) -> Vec<String> {
+    let mut names: Vec<String> = codes.keys().cloned().collect();
+    names.sort();
+    names.dedup();
+
+    names
+}
+
+/// Parse the output of `xmodmap -pke` into a map of key names to key codes.
+///
+/// Each key name listed for a key code is included. Lines that do not match the expected
+/// `keycode <code> = <names ...>` format are skipped.
+///
+/// X key codes are a single byte (the X server uses 8..=255) so lines giving a key code above
+/// 255, which some extended keyboards and xkb setups can produce, are skipped with a warning
+/// rather than being truncated to a code for a different key.
+///
+/// Some keyboards list the same key name against more than one key code (e.g. `ISO_Level3_Shift`
+/// being on both the right Alt key and a dedicated key). When this happens the lowest key code is
+/// always used so that bindings grab the same physical key every time.
+///
+/// ```
+/// # use penrose::core::helpers::parse_xmodmap_output;
+/// let codes = parse_xmodmap_output("keycode  10 = 1 exclam 1 exclam\nkeycode  36 = Return");
+///
+/// assert_eq!(codes.get("exclam"), Some(&10));
+/// assert_eq!(codes.get("Return"), Some(&36));
+/// ```
+pub fn parse_xmodmap_output(input: &str) -> CodeMap {
+    code_map_from_names(input.lines().flat_map(keycodes_from_xmodmap_line))
+}
+
+/// Parse the output of `xkbcomp -xkb $DISPLAY -` into a map of key names to key codes.
+///
+/// Key codes are taken from the `<NAME> = <code>;` entries (and aliases) in the `xkb_keycodes`
+/// section and key names from the symbol lists of each `key <NAME> { ... };` entry in the
+/// `xkb_symbols` section. Conflicting key names are resolved in the same way as
+/// [parse_xmodmap_output].
+///
+/// ```
+/// # use penrose::core::helpers::parse_xkb_keymap;
+/// let keymap = r#"
+/// xkb_keycodes "evdev" {
+///     <AE01> = 10;
+///     <RTRN> = 36;
+/// };
+/// xkb_symbols "pc+us" {
+///     key <AE01> { [ 1, exclam ] };
+///     key <RTRN> { [ Return ] };
+/// };
+/// "#;
+/// let codes = parse_xkb_keymap(keymap);
+///
+/// assert_eq!(codes.get("exclam"), Some(&10));
+/// assert_eq!(codes.get("Return"), Some(&36));
+/// ```
+pub fn parse_xkb_keymap(input: &str) -> CodeMap {
+    let mut key_codes: HashMap<&str, u8> = HashMap::new();
+    let mut aliases: Vec<(&str, &str)> = Vec::new();
+
+    for line in input.lines().map(str::trim) {
+        let entry = match line.strip_suffix(';') {
+            Some(entry) => entry,
+            None => continue,
+        };
+        let (lhs, rhs) = match entry.split_once('=') {
+            Some((lhs, rhs)) => (lhs.trim(), rhs.trim()),
+            None => continue,
+        };
+
+        if let Some(alias) = lhs.strip_prefix("alias") {
+            aliases.push((xkb_key_name(alias.trim()), xkb_key_name(rhs)));
+        } else if lhs.starts_with('<') {
+            if let Ok(code) = rhs.parse() {
+                key_codes.insert(xkb_key_name(lhs), code);
+            }
+        }
+    }
+
+    for (alias, target) in aliases {
+        if let Some(&code) = key_codes.get(target) {
+            key_codes.insert(alias, code);
+        }
+    }
+
+    let symbols = match input.find("xkb_symbols") {
+        Some(ix) => &input[ix..],
+        None => return CodeMap::new(),
+    };
+
+    let mut names = Vec::new();
+    for block in symbols.split("key <").skip(1) {
+        let (key, rest) = match block.split_once('>') {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let body = match (rest.find('{'), rest.find('}')) {
+            (Some(start), Some(end)) if start < end => &rest[start + 1..end],
+            _ => continue,
+        };
+
+        if let Some(&code) = key_codes.get(key) {
+            let mut seen: Vec<&str> = Vec::new();
+            for name in xkb_symbol_names(body) {
+                if !seen.contains(&name) {
+                    seen.push(name);
+                    names.push((name.to_string(), code));
+                }
+            }
+        }
+    }
+
+    code_map_from_names(names.into_iter())
+}
+
+// '<AE01>' -> 'AE01'
+fn xkb_key_name(s: &str) -> &str {
+    s.trim().trim_start_matches('<').trim_end_matches('>')
+}
+
+// The keysym names in the body of an xkb 'key' entry: either a bare list ('[ 1, exclam ]') or
+// a list assigned to 'symbols[GroupN]'. Other lists (such as 'actions[GroupN]') are skipped.
+fn xkb_symbol_names(body: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut start = 0;
+
+    while let Some(open) = body[start..].find('[').map(|ix| ix + start) {
+        let close = match body[open..].find(']') {
+            Some(ix) => ix + open,
+            None => break,
+        };
+        let label = body[..open].rsplit([',', '{']).next().unwrap_or("");
+        let label = label.trim().trim_end_matches('=').trim();
+        let is_index = body[..open].ends_with(|c: char| c.is_alphanumeric());
+
+        if !is_index && (label.is_empty() || label.starts_with("symbols[")) {
+            names.extend(
+                body[open + 1..close]
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty()),
+            );
+        }
+        start = close + 1;
+    }
+
+    names
+}
+
+/// Layer one [CodeMap] on top of another, with the entries in `overrides` taking precedence.
+///
+/// Every key name in either map is present in the result. Where a name is in both maps the key
+/// code from `overrides` is used, allowing a small host specific set of key codes to be applied
+/// on top of a shared keymap:
+///
+/// ```
+/// # use penrose::core::helpers::{merge_codemaps, parse_xmodmap_output};
+/// let base = parse_xmodmap_output("keycode  36 = Return\nkeycode 191 = XF86Tools");
+/// let overrides = parse_xmodmap_output("keycode 192 = XF86Tools");
+/// let codes = merge_codemaps(base, overrides);
+///
+/// assert_eq!(codes.get("Return"), Some(&36));
+/// assert_eq!(codes.get("XF86Tools"), Some(&192));
+/// ```
+pub fn merge_codemaps(mut base: CodeMap, overrides: CodeMap) -> CodeMap {
+    base.extend(overrides);
+    base
+}
+
+// Build a CodeMap from (name, code) pairs, keeping the lowest code for names that are listed
+// against more than one key.
+fn code_map_from_names(names: impl Iterator<Item = (String, u8)>) -> CodeMap {
+    let mut codes = CodeMap::new();
+    for (name, code) in names {
+        let current = codes.entry(name).or_insert(code);
+        *current = (*current).min(code);
     }
+
+    codes
+}
+
+// Some versions of xmodmap annotate keysym names with the character they produce in parentheses,
+// e.g. 'keycode  48 = adiaeresis (ä) Adiaeresis (Ä)'. These are not key names so strip them out
+// before splitting the line into words.
+pub(crate) fn strip_xmodmap_annotations(line: &str) -> String {
+    let mut depth = 0;
+    line.chars()
+        .filter(|&c| match c {
+            '(' => {
+                depth += 1;
+                false
+            }
+            ')' if depth > 0 => {
+                depth -= 1;
+                false
+            }
+            _ => depth == 0,
+        })
+        .collect()
+}
+
+// Parse a single line of 'xmodmap -pke' output of the form 'keycode <code> = <names ...>' into
+// the key code and the name listed for each shift level (including repeats), skipping lines
+// that are not in that format.
+//
+// X key codes are a single byte so lines for larger key codes are skipped with a warning: a
+// truncated key code would grab the wrong key.
+pub(crate) fn xmodmap_line_levels(line: &str) -> Option<(u8, Vec<String>)> {
+    let stripped = strip_xmodmap_annotations(line);
+    let mut words = stripped.split_whitespace();
+    let key_code: Option<u32> = match (words.next(), words.next(), words.next()) {
+        (Some("keycode"), Some(code), Some("=")) => code.parse().ok(),
+        (None, _, _) => return None, // blank line
+        _ => None,
+    };
+
+    match key_code.map(u8::try_from) {
+        Some(Err(_)) => {
+            warn!(
+                ?line,
+                "skipping key code above 255: X key codes are a single byte"
+            );
+            None
+        }
+        Some(Ok(code)) => Some((code, words.map(String::from).collect())),
+        None => {
+            debug!(?line, "skipping malformed line in xmodmap output");
+            None
+        }
+    }
+}
+
+// Parse a single line of 'xmodmap -pke' output using xmodmap_line_levels. xmodmap lists a name
+// for each shift level so the same name is often repeated: names are returned once each, in order
+// of the lowest level they appear at, so the base name always comes first.
+fn keycodes_from_xmodmap_line(line: &str) -> Vec<(String, u8)> {
+    let (code, levels) = match xmodmap_line_levels(line) {
+        Some(parsed) => parsed,
+        None => return vec![],
+    };
+
+    let mut names: Vec<(String, u8)> = Vec::new();
+    for name in levels {
+        if !names.iter().any(|(n, _)| *n == name) {
+            names.push((name, code));
+        }
+    }
+
+    names
+}
+
+/// Read the system keymap, re-using the result of a previous call if there is one.
+///
+/// The first call behaves exactly like [keycodes_from_keymap] (so the keymap is read using the
+/// [KeymapSource] selected by [keymap_source_from_env], which is xmodmap by default) but the
+/// parsed result is cached so that later calls do not need to spawn a new process. Call
+/// [invalidate_keycode_cache] after changing your keymap to force the next call to re-read it.
+///
+/// # Panics
+/// This function will panic under the same conditions as [keycodes_from_keymap].
+pub fn keycodes_from_xmodmap_cached() -> CodeMap {
+    let mut cache = KEYCODE_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache.get_or_insert_with(keycodes_from_keymap).clone()
+}
+
+/// Drop the keymap cached by [keycodes_from_xmodmap_cached] so that it is re-read on next use
+pub fn invalidate_keycode_cache() {
+    *KEYCODE_CACHE.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Re-read the system keymap using [try_keycodes_from_keymap], replacing the keymap cached by
+/// [keycodes_from_xmodmap_cached] with the result.
+///
+/// If the keymap can not be read then the cache is dropped (as with [invalidate_keycode_cache])
+/// rather than being left holding the old keymap.
+pub fn reload_keycode_cache() -> std::result::Result<CodeMap, XmodmapError> {
+    let mut cache = KEYCODE_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    *cache = None;
+    let codes = try_keycodes_from_keymap()?;
+    *cache = Some(codes.clone());
+
+    Ok(codes)
+}
+
+/// Register a closure to be run if penrose panics, before the process exits.
+///
+/// Fatal errors in penrose (such as [keycodes_from_xmodmap] failing) are raised as panics which
+/// would otherwise leave things like a running status bar or grabbed keys behind. The panic is
+/// logged, then `cleanup` is run, and then the previously installed panic hook (by default the
+/// one that prints the panic message) is called.
+///
+/// Only the most recently registered closure is kept and it is run at most once, so a panic
+/// inside of `cleanup` itself will not recurse.
+///
+/// ```no_run
+/// # use penrose::core::helpers::{set_panic_cleanup, spawn};
+/// set_panic_cleanup(Box::new(|| {
+///     let _ = spawn("pkill polybar");
+/// }));
+/// ```
+pub fn set_panic_cleanup(cleanup: Box<dyn Fn() + Send>) {
+    *PANIC_CLEANUP.lock().unwrap_or_else(|e| e.into_inner()) = Some(cleanup);
+
+    PANIC_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            error!(%info, "penrose panicked: running cleanup");
+            let cleanup = PANIC_CLEANUP
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .take();
+            if let Some(f) = cleanup {
+                f();
+            }
+            previous(info);
+        }));
+    });
 }
 
 /// Create a Vec of index selectors for the given input slice
@@ -142,3 +1263,513 @@ pub fn index_selectors<'a, T>(len: usize) -> Vec<Selector<'a, T>> {
 pub fn logging_error_handler() -> ErrorHandler {
     Box::new(|e: PenroseError| error!("{}", e))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    test_cases! {
+        split_args;
+        args: (cmd: &str, expected: Vec<&str>);
+
+        case: single_word => ("dmenu_run", vec!["dmenu_run"]);
+        case: unquoted => ("xrandr --output DP-1 --off", vec!["xrandr", "--output", "DP-1", "--off"]);
+        case: extra_whitespace => ("  a   b\tc ", vec!["a", "b", "c"]);
+        case: double_quotes => (r#"notify-send "hello world""#, vec!["notify-send", "hello world"]);
+        case: single_quotes => ("notify-send 'hello world'", vec!["notify-send", "hello world"]);
+        case: nested_quotes => (r#"echo "it's" 'say "hi"'"#, vec!["echo", "it's", r#"say "hi""#]);
+        case: escaped_space => (r"ls my\ file", vec!["ls", "my file"]);
+        case: escaped_quote => (r#"echo \"hi\""#, vec!["echo", r#""hi""#]);
+        case: empty_quotes => (r#"echo """#, vec!["echo", ""]);
+        case: quotes_mid_word => (r#"--title="my term""#, vec!["--title=my term"]);
+
+        body: {
+            assert_eq!(split_args(cmd), expected);
+        }
+    }
+
+    test_cases! {
+        shell_quote;
+        args: (s: &str, expected: &str);
+
+        case: plain => ("hello", "'hello'");
+        case: empty => ("", "''");
+        case: whitespace => ("hello world", "'hello world'");
+        case: single_quotes => ("it's", r"'it'\''s'");
+        case: only_a_quote => ("'", r"''\'''");
+        case: double_quotes => (r#"say "hi""#, r#"'say "hi"'"#);
+        case: shell_syntax => ("a; rm -rf ~ && $(id) `id` | *", "'a; rm -rf ~ && $(id) `id` | *'");
+        body: {
+            assert_eq!(shell_quote(s), expected);
+        }
+    }
+
+    #[test]
+    fn shell_quoted_strings_are_passed_to_the_shell_unchanged() {
+        for s in [
+            "it's",
+            r#"a "b" 'c' \d"#,
+            "x; echo injected",
+            "$HOME `id` *",
+            "",
+            "\\n",
+        ] {
+            let output = Command::new("sh")
+                .arg("-c")
+                .arg(format!("printf %s {}", shell_quote(s)))
+                .output()
+                .expect("sh should be available");
+
+            assert_eq!(String::from_utf8_lossy(&output.stdout), s);
+        }
+    }
+
+    test_cases! {
+        expand_env_vars;
+        args: (s: &str, expected: &str);
+
+        case: no_vars => ("dmenu_run -p run", "dmenu_run -p run");
+        case: bare_var => ("ls $HOME", "ls /home/user");
+        case: braced_var => ("ls ${HOME}/bin", "ls /home/user/bin");
+        case: var_mid_word => ("--dir=$HOME/x", "--dir=/home/user/x");
+        case: multiple_vars => ("$EDITOR ${HOME}", "vim /home/user");
+        case: unknown_var => ("echo $MISSING end", "echo  end");
+        case: unknown_braced_var => ("echo ${MISSING}end", "echo end");
+        case: lone_dollar => ("echo $ 5", "echo $ 5");
+        case: trailing_dollar => ("echo $", "echo $");
+        case: digit_after_dollar => ("echo $5", "echo $5");
+        case: unterminated_brace => ("echo ${HOME", "echo ${HOME");
+        case: empty_braces => ("echo ${}", "echo ${}");
+        case: invalid_braced_name => ("echo ${A-B}", "echo ${A-B}");
+        case: expansion_before_splitting => ("ls $ARGS", "ls -l -a");
+
+        body: {
+            let lookup = |name: &str| match name {
+                "HOME" => Some("/home/user".to_string()),
+                "EDITOR" => Some("vim".to_string()),
+                "ARGS" => Some("-l -a".to_string()),
+                _ => None,
+            };
+            assert_eq!(expand_env_vars(s, lookup), expected);
+        }
+    }
+
+    test_cases! {
+        xmodmap_line_levels;
+        args: (line: &str, expected: Option<(u8, Vec<&str>)>);
+
+        case: single_name => ("keycode  36 = Return", Some((36, vec!["Return"])));
+        case: repeats_are_kept => (
+            "keycode  10 = 1 1 onesuperior",
+            Some((10, vec!["1", "1", "onesuperior"]))
+        );
+        case: annotations_are_stripped => (
+            "keycode  48 = adiaeresis (\u{e4}) Adiaeresis (\u{c4})",
+            Some((48, vec!["adiaeresis", "Adiaeresis"]))
+        );
+        case: no_names => ("keycode   8 =", Some((8, vec![])));
+        case: blank => ("   ", None);
+        case: code_out_of_range => ("keycode 300 = Return", None);
+        case: garbage => ("some unexpected output", None);
+        body: {
+            let expected = expected
+                .map(|(code, names)| (code, names.into_iter().map(String::from).collect()));
+            assert_eq!(xmodmap_line_levels(line), expected);
+        }
+    }
+
+    test_cases! {
+        keycodes_from_xmodmap_line;
+        args: (line: &str, expected: Vec<(&str, u8)>);
+
+        case: single_name => ("keycode  36 = Return", vec![("Return", 36)]);
+        case: multiple_names => (
+            "keycode  10 = 1 exclam 1 exclam",
+            vec![("1", 10), ("exclam", 10)]
+        );
+        case: repeated_levels => ("keycode  38 = a A a A", vec![("a", 38), ("A", 38)]);
+        case: base_name_first => ("keycode  38 = A a A a", vec![("A", 38), ("a", 38)]);
+        case: no_names => ("keycode   8 =", vec![]);
+        case: blank => ("   ", vec![]);
+        case: non_numeric_code => ("keycode  abc = Return", vec![]);
+        case: code_out_of_range => ("keycode 300 = Return", vec![]);
+        case: missing_equals => ("keycode 36 Return", vec![]);
+        case: garbage => ("some unexpected output", vec![]);
+        body: {
+            let expected: Vec<(String, u8)> = expected
+                .into_iter()
+                .map(|(name, code)| (name.to_string(), code))
+                .collect();
+            assert_eq!(keycodes_from_xmodmap_line(line), expected);
+        }
+    }
+
+    #[test]
+    fn merged_codemaps_prefer_overrides() {
+        let base = map! {
+            "Return".to_string() => 36,
+            "XF86Tools".to_string() => 191,
+        };
+        let overrides = map! {
+            "XF86Tools".to_string() => 192,
+            "XF86Launch5".to_string() => 193,
+        };
+
+        let expected = map! {
+            "Return".to_string() => 36,
+            "XF86Tools".to_string() => 192,
+            "XF86Launch5".to_string() => 193,
+        };
+
+        assert_eq!(merge_codemaps(base, overrides), expected);
+    }
+
+    #[test]
+    fn merging_empty_codemaps_keeps_the_other_map() {
+        let codes: CodeMap = map! { "Return".to_string() => 36, };
+
+        assert_eq!(merge_codemaps(codes.clone(), CodeMap::new()), codes);
+        assert_eq!(merge_codemaps(CodeMap::new(), codes.clone()), codes);
+    }
+
+    #[test]
+    fn codes_and_names_are_grouped_by_key_code() {
+        let output = "
+keycode   8 =
+keycode  36 = Return NoSymbol Return
+not a keycode line
+keycode  10 = 1 exclam 1 exclam
+keycode  10 = onesuperior 1
+keycode 108 = ISO_Level3_Shift
+keycode  92 = ISO_Level3_Shift
+";
+
+        let expected: Vec<(u8, Vec<String>)> = vec![
+            (10, vec!["1".into(), "exclam".into(), "onesuperior".into()]),
+            (36, vec!["Return".into(), "NoSymbol".into()]),
+            (92, vec!["ISO_Level3_Shift".into()]),
+            (108, vec!["ISO_Level3_Shift".into()]),
+        ];
+
+        assert_eq!(parse_code_and_names(output), expected);
+    }
+
+    #[test]
+    fn xmodmap_output_is_parsed() {
+        let output = "
+keycode   8 =
+keycode   9 = Escape NoSymbol Escape
+keycode  10 = 1 exclam 1 exclam
+
+keycode  36 = Return NoSymbol Return
+not a keycode line
+keycode 133 = Super_L NoSymbol Super_L
+";
+        let codes = parse_xmodmap_output(output);
+        let expected: CodeMap = map! {
+            "Escape".to_string() => 9,
+            "1".to_string() => 10,
+            "exclam".to_string() => 10,
+            "Return".to_string() => 36,
+            "Super_L".to_string() => 133,
+            "NoSymbol".to_string() => 9,
+        };
+
+        assert_eq!(codes, expected);
+    }
+
+    #[test]
+    fn parenthesised_xmodmap_annotations_are_ignored() {
+        let output = "
+keycode  48 = adiaeresis (ä) Adiaeresis (Ä) adiaeresis Adiaeresis
+keycode  35 = dead_diaeresis (dead key) dead_abovering
+";
+        let codes = parse_xmodmap_output(output);
+        let expected: CodeMap = map! {
+            "adiaeresis".to_string() => 48,
+            "Adiaeresis".to_string() => 48,
+            "dead_diaeresis".to_string() => 35,
+            "dead_abovering".to_string() => 35,
+        };
+
+        assert_eq!(codes, expected);
+    }
+
+    #[test]
+    fn xkb_keymaps_are_parsed() {
+        let keymap = r#"
+xkb_keymap {
+xkb_keycodes "evdev+aliases(qwerty)" {
+    minimum = 8;
+    maximum = 255;
+    <ESC>                = 9;
+    <AE01>               = 10;
+    <LFSH>               = 50;
+    <BKSL>               = 51;
+    <RALT>               = 108;
+    alias <AC12>         = <BKSL>;
+    indicator 1 = "Caps Lock";
+};
+xkb_types "complete" {
+    virtual_modifiers NumLock,Alt;
+    type "ONE_LEVEL" {
+        modifiers= none;
+        level_name[Level1]= "Any";
+    };
+};
+xkb_symbols "pc+gb+inet(evdev)" {
+    name[group1]="English (UK)";
+
+    key <ESC>                {	[          Escape ] };
+    key <AE01>               {	[               1,          exclam,  1, exclam ] };
+    key <LFSH> {
+        type= "TWO_LEVEL",
+        symbols[Group1]= [ Shift_L, Shift_L ]
+    };
+    key <AC12>               {	[      numbersign,      asciitilde ] };
+    key <RALT> {
+        type= "ONE_LEVEL",
+        symbols[Group1]= [ ISO_Level3_Shift ],
+        actions[Group1]= [ SetMods(modifiers=LevelThree) ]
+    };
+    key <UNKN>               {	[          unknown ] };
+    modifier_map Shift { <LFSH> };
+};
+};
+"#;
+        let codes = parse_xkb_keymap(keymap);
+        let expected: CodeMap = map! {
+            "Escape".to_string() => 9,
+            "1".to_string() => 10,
+            "exclam".to_string() => 10,
+            "Shift_L".to_string() => 50,
+            "numbersign".to_string() => 51,
+            "asciitilde".to_string() => 51,
+            "ISO_Level3_Shift".to_string() => 108,
+        };
+
+        assert_eq!(codes, expected);
+    }
+
+    #[test]
+    fn keymaps_without_symbols_are_empty() {
+        assert_eq!(
+            parse_xkb_keymap("xkb_keycodes { <ESC> = 9; };"),
+            CodeMap::new()
+        );
+    }
+
+    #[test]
+    fn key_names_are_sorted() {
+        let codes = parse_xmodmap_output(
+            "keycode  36 = Return NoSymbol Return\nkeycode  10 = 1 exclam 1 exclam",
+        );
+
+        assert_eq!(
+            sorted_key_names(&codes),
+            vec!["1", "NoSymbol", "Return", "exclam"]
+        );
+    }
+
+    #[test]
+    fn keymap_sources_group_names_by_code_by_default() {
+        struct Fixed;
+        impl KeymapSource for Fixed {
+            fn code_map(&self) -> std::result::Result<CodeMap, XmodmapError> {
+                Ok(map! { "j".into() => 44, "Return".into() => 36, "KP_Enter".into() => 36, })
+            }
+        }
+
+        assert_eq!(
+            Fixed.code_and_names().unwrap(),
+            vec![
+                (36, vec!["KP_Enter".to_string(), "Return".to_string()]),
+                (44, vec!["j".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn out_of_range_xmodmap_key_codes_are_skipped() {
+        let output = "
+keycode 255 = XF86Launch9
+keycode 256 = XF86Launch5
+keycode 300 = XF86Launch6 XF86Launch9
+keycode 99999999999 = XF86Launch7
+";
+        let codes = parse_xmodmap_output(output);
+
+        assert_eq!(codes.get("XF86Launch9"), Some(&255));
+        assert_eq!(codes.get("XF86Launch5"), None);
+        assert_eq!(codes.get("XF86Launch6"), None);
+        assert_eq!(codes.get("XF86Launch7"), None);
+        assert_eq!(parse_code_and_names(output).len(), 1);
+    }
+
+    #[test]
+    fn duplicate_xmodmap_key_names_use_the_lowest_key_code() {
+        let output = "
+keycode 108 = ISO_Level3_Shift NoSymbol ISO_Level3_Shift
+keycode  92 = ISO_Level3_Shift NoSymbol ISO_Level3_Shift
+keycode 203 = ISO_Level3_Shift NoSymbol ISO_Level3_Shift
+";
+        let codes = parse_xmodmap_output(output);
+
+        assert_eq!(codes.get("ISO_Level3_Shift"), Some(&92));
+    }
+
+    #[test]
+    fn executables_are_resolved_using_the_first_argument() {
+        let path = resolve_executable("sh -c 'echo hello'").expect("sh should be on the PATH");
+        assert!(path.ends_with("sh"));
+        assert_eq!(
+            resolve_executable(path.to_str().unwrap()),
+            Some(path.clone())
+        );
+    }
+
+    #[test]
+    fn unknown_executables_are_not_resolved() {
+        assert_eq!(
+            resolve_executable("penrose-not-a-real-program --flag"),
+            None
+        );
+        assert_eq!(resolve_executable("/not/a/real/program"), None);
+        assert_eq!(resolve_executable(""), None);
+    }
+
+    #[test]
+    fn spawn_dry_run_is_disabled_by_default() {
+        assert!(!spawn_dry_run());
+    }
+
+    #[test]
+    fn spawn_errors_include_the_command() {
+        match spawn("penrose-not-a-real-program --new-window") {
+            Err(PenroseError::SpawnFailed(cmd, reason)) => {
+                assert_eq!(cmd, "penrose-not-a-real-program --new-window");
+                assert!(reason.contains("is it installed and on your PATH?"));
+            }
+            res => panic!("expected a SpawnFailed error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn empty_commands_are_an_error() {
+        for cmd in &["", "   "] {
+            match spawn(*cmd) {
+                Err(PenroseError::SpawnFailed(c, _)) => assert_eq!(&c, cmd),
+                res => panic!("expected a SpawnFailed error, got {:?}", res),
+            }
+            assert!(spawn_for_output(*cmd).is_err());
+            assert!(spawn_for_output_timeout(*cmd, Duration::from_secs(1)).is_err());
+            assert!(spawn_with_env(*cmd, vec![]).is_err());
+            assert!(spawn_detached(*cmd).is_err());
+            assert!(spawn_inherit_io(*cmd).is_err());
+        }
+    }
+
+    #[test]
+    fn shell_commands_are_not_split() {
+        let dir = env::temp_dir().join(format!("penrose-spawn-shell-{}", std::process::id()));
+        let out = dir.join("out");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let cmd = format!("echo 'hello  world' | tr a-z A-Z > '{}'", out.display());
+        spawn_shell(cmd).unwrap();
+
+        let mut contents = String::new();
+        for _ in 0..50 {
+            contents = std::fs::read_to_string(&out).unwrap_or_default();
+            if !contents.is_empty() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(contents, "HELLO  WORLD\n");
+        assert!(spawn_shell("  ").is_err());
+    }
+
+    #[test]
+    fn output_is_returned_if_the_command_finishes_in_time() {
+        let res = spawn_for_output_timeout("echo 'hello world'", Duration::from_secs(5));
+        assert_eq!(res.unwrap(), "hello world\n");
+    }
+
+    #[test]
+    fn commands_are_killed_after_the_timeout() {
+        let timeout = Duration::from_millis(50);
+        let start = std::time::Instant::now();
+
+        match spawn_for_output_timeout("sleep 10", timeout) {
+            Err(PenroseError::SpawnTimeout(cmd, t)) => {
+                assert_eq!(cmd, "sleep 10");
+                assert_eq!(t, timeout);
+            }
+            res => panic!("expected a SpawnTimeout error, got {:?}", res),
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    test_cases! {
+        program_basename;
+        args: (program: &str, expected: &str);
+
+        case: bare_name => ("polybar", "polybar");
+        case: absolute_path => ("/usr/bin/polybar", "polybar");
+        case: relative_path => ("./bin/picom", "picom");
+        body: {
+            assert_eq!(program_basename(program), expected);
+        }
+    }
+
+    #[test]
+    fn spawn_pid_returns_none_on_failure() {
+        assert_eq!(spawn_pid("penrose-not-a-real-program --new-window"), None);
+        assert_eq!(spawn_pid(""), None);
+    }
+
+    #[test]
+    fn running_processes_are_found_and_terminated() {
+        let pid = spawn_pid("sleep 30").unwrap() as i32;
+
+        // The command line of the child is briefly empty while it is being exec'd
+        let start = Instant::now();
+        while process_name(pid).is_none() && start.elapsed() < Duration::from_secs(1) {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(pids_for_program("sleep").contains(&pid));
+        assert!(!pids_for_program("penrose-not-a-real-program").contains(&pid));
+
+        terminate(&[pid]);
+        assert!(!pids_for_program("sleep").contains(&pid));
+    }
+
+    #[test]
+    fn running_shells_are_not_terminated_when_respawning_a_shell() {
+        let pid = spawn_pid("sh -c 'sleep 30; true'").unwrap() as i32;
+
+        let start = Instant::now();
+        while process_name(pid).is_none() && start.elapsed() < Duration::from_secs(1) {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        respawn("sh -c true").unwrap();
+        assert!(pids_for_program("sh").contains(&pid));
+
+        terminate(&[pid]);
+    }
+
+    #[test]
+    fn detached_spawn_errors_include_the_command() {
+        match spawn_detached("penrose-not-a-real-program --new-window") {
+            Err(PenroseError::SpawnFailed(cmd, _)) => {
+                assert_eq!(cmd, "penrose-not-a-real-program --new-window")
+            }
+            res => panic!("expected a SpawnFailed error, got {:?}", res),
+        }
+    }
+}
@@ -146,10 +146,18 @@ macro_rules! map {
 /// "M-j" => run_internal!(cycle_client, Forward);
 /// "M-S-j" => run_internal!(drag_client, Forward);
 /// "M-Return" => run_external!("alacritty");
+/// "XF86AudioRaiseVolume" => run_external!("amixer set Master 5%+");
 /// # }};
 /// ```
 ///
-/// This is what the majority of your keybindings will look like.
+/// This is what the majority of your keybindings will look like. Modifiers are optional: a bare
+/// key name such as `XF86AudioRaiseVolume` is bound with no modifiers held. Keys that have no name
+/// in your keymap can be bound using their raw X key code in place of the key name: `M-code:191`
+/// (or `M-code:0xbf` / `M-code:0o277` if your tools print key codes in hex or octal).
+///
+/// Multi-key chords such as `M-x g` are not accepted here and are rejected by the compile time
+/// validation: a [KeyChord][5] can only be built at runtime using [parse_key_chord][6] and chords
+/// are not yet dispatched by the [WindowManager][2].
 ///
 /// Should be a string literal and an expression that satisfies the [KeyEventHandler][1] type. The
 /// [run_internal] and [run_external] helper macros can be used for simplifying bindings that
@@ -175,6 +183,11 @@ macro_rules! map {
 ///     "M-{}" => focus_workspace (REF);
 ///     "M-S-{}" => client_to_workspace (REF);
 /// };
+///
+/// // Numeric key names can be given as a range
+/// map: { "1"..="9", } to index_selectors(9) => {
+///     "M-C-{}" => client_to_screen (REF);
+/// };
 /// # }};
 /// ```
 ///
@@ -187,26 +200,311 @@ macro_rules! map {
 /// }
 /// ```
 ///
+/// The `{}` placeholder can appear anywhere in the template rather than only as the key name, so
+/// templates such as `"M-F{}"` or `"{}-Return"` (with modifiers as the names being mapped) work
+/// as expected:
+/// ```no_run
+/// # #[macro_use] extern crate penrose;
+/// # use penrose::__example_helpers::*;
+/// # fn example() -> ExampleKeyBindings {
+/// # gen_keybindings! {
+/// map: { "1"..="4" } to index_selectors(4) => {
+///     "M-F{}" => focus_workspace (REF);
+/// };
+/// # }};
+/// ```
+///
+/// Templates can also contain more than one `{}` placeholder, in which case a list of key names
+/// must be given for each of them. Each placeholder is replaced by a name from the corresponding
+/// list and a binding is generated for every combination of names, with the names from the last
+/// list changing fastest: `"M-{}-{}"` with `{ "A", "C" } { "1", "2" }` expands to `M-A-1`,
+/// `M-A-2`, `M-C-1` and `M-C-2`. The values from the iterator are paired with the bindings in
+/// that order:
+/// ```no_run
+/// # #[macro_use] extern crate penrose;
+/// # use penrose::__example_helpers::*;
+/// # fn example() -> ExampleKeyBindings {
+/// # gen_keybindings! {
+/// // M-A-{1,2,3} focus workspaces 1 to 3 and M-C-{1,2,3} focus workspaces 4 to 6
+/// map: { "A", "C" } { "1"..="3" } to index_selectors(6) => {
+///     "M-{}-{}" => focus_workspace (REF);
+/// };
+/// # }};
+/// ```
+///
+/// Numeric key names can be given as a range of string literals rather than listing each one:
+/// `"1"..="9"` is equivalent to `"1", "2", ... "9"` and (as with normal rust ranges) `"0".."5"`
+/// excludes the upper bound. Ranges and literals can be mixed in the same list:
+/// ```markdown
+/// map: { "1"..="9", "0" } to index_selectors(10) => { ... }
+/// ```
+///
+/// A trailing comma after the last key name is accepted and empty string literals are ignored,
+/// so long lists of key names can be split into groups using `""` as a spacer.
+///
 /// Note that the key names _must_ be string literals, not just `&str` references. The arguments to
 /// the [WindowManager][2] method can be passed by reference using `REF` or by value using `VAL`.
 /// Any additional arguments can be passed explicitly if they are required by the method.
 ///
+/// ### Extra modifiers
+///
+/// ```no_run
+/// # #[macro_use] extern crate penrose;
+/// # use penrose::__example_helpers::*;
+/// # fn example() -> ExampleKeyBindings {
+/// # gen_keybindings! {
+/// extra_modifiers: { "H" };
+///
+/// "H-j" => run_internal!(cycle_client, Forward);
+/// # }};
+/// ```
+///
+/// By default only the `M`, `A`, `C` and `S` modifiers (or their long forms) are accepted. If you
+/// have remapped a key to act as Hyper (mod3) then you can enable the `H` (or `Hyper`) modifier
+/// using an `extra_modifiers` block.
+///
+/// ### Modifier maps
+///
+/// ```no_run
+/// # #[macro_use] extern crate penrose;
+/// # use penrose::__example_helpers::*;
+/// # use penrose::core::bindings::ModifierMap;
+/// # fn example() -> ExampleKeyBindings {
+/// # gen_keybindings! {
+/// modifier_map: ModifierMap { meta: 1 << 5, ..Default::default() };
+///
+/// "M-j" => run_internal!(cycle_client, Forward);
+/// # }};
+/// ```
+///
+/// Bindings are parsed using the default [ModifierMap][3] which expects Super to be on mod4.
+/// If your modifiers are mapped differently then a `modifier_map` block sets the [ModifierMap][3]
+/// used for the remaining bindings.
+///
+/// ### Modifier aliases
+///
+/// ```no_run
+/// # #[macro_use] extern crate penrose;
+/// # use penrose::__example_helpers::*;
+/// # fn example() -> ExampleKeyBindings {
+/// # gen_keybindings! {
+/// modifier_aliases: { "super_shift" => "M-S" };
+///
+/// "super_shift-j" => run_internal!(drag_client, Forward);
+/// "super_shift-k" => run_internal!(drag_client, Backward);
+/// # }};
+/// ```
+///
+/// Bindings that share a set of modifiers can give that set a name using a `modifier_aliases`
+/// block. Any modifier in a binding that matches the name of an alias is replaced by the
+/// modifiers it stands for (see [expand_modifier_aliases][4]) so `super_shift-j` is bound as
+/// `M-S-j`. Aliases apply to every binding (including those generated by `map` blocks) so the
+/// `modifier_aliases` block must come first.
+///
+/// ### Modifier groups
+///
+/// ```no_run
+/// # #[macro_use] extern crate penrose;
+/// # use penrose::__example_helpers::*;
+/// # fn example() -> ExampleKeyBindings {
+/// # gen_keybindings! {
+/// "M-[S,C]-j" => run_internal!(cycle_client, Forward);
+/// "M-[,S]-Return" => run_external!("alacritty");
+/// "M-(KP_Add|equal)" => run_internal!(update_max_main, More);
+/// # }};
+/// ```
+///
+/// A direct binding can bind the same action to several sets of modifiers by giving a comma
+/// separated group of alternatives in square brackets: `M-[S,C]-j` binds both `M-S-j` and
+/// `M-C-j`. An empty alternative leaves that modifier out, so `M-[,S]-Return` binds `M-Return` and
+/// `M-S-Return`. Several keys can be bound in the same way by giving a `|` separated group of key
+/// names in parentheses: `M-(Return|KP_Enter)` binds both `M-Return` and `M-KP_Enter`. Each of
+/// the expanded bindings is checked for duplicates against the rest of your bindings. Groups are
+/// not supported in `map` block templates.
+///
+/// ### Disabled bindings
+///
+/// ```no_run
+/// # #[macro_use] extern crate penrose;
+/// # use penrose::__example_helpers::*;
+/// # fn example() -> ExampleKeyBindings {
+/// # gen_keybindings! {
+/// "#M-j" => run_internal!(cycle_client, Forward);
+///
+/// map: { "1"..="9" } to index_selectors(9) => {
+///     "#M-S-{}" => client_to_workspace (REF);
+/// };
+/// # }};
+/// ```
+///
+/// Prefixing a binding (or a template in a `map` block) with `#` disables it without having to
+/// remove it from your config. Disabled bindings are not validated and are not bound.
+///
+/// ### Tap bindings
+///
+/// ```no_run
+/// # #[macro_use] extern crate penrose;
+/// # use penrose::__example_helpers::*;
+/// # fn example() -> ExampleKeyBindings {
+/// # gen_keybindings! {
+/// "tap:M" => run_external!("dmenu_run");
+/// # }};
+/// ```
+///
+/// A binding of the form `tap:<modifier>` runs when the left hand key for that modifier is pressed
+/// and released on its own without any other keys being pressed while it is held. The release
+/// needs to happen within [Config::tap_timeout_ms][crate::core::config::Config] of the press so it
+/// should be shorter than your autorepeat delay. While the modifier is bound in this way, other
+/// keys pressed while holding it are delivered to penrose rather than the focused client, even
+/// if they are not bound.
+///
+/// # Validation
+///
+/// All bindings are checked at compile time for invalid modifiers, unknown key names and
+/// duplicates. Key names are checked against the full set of X11 keysym names (as defined in
+/// `keysymdef.h`) rather than your current keyboard layout, so a config that compiles on one
+/// machine will compile on any other. Bindings are resolved to key codes using the current layout
+/// when the macro runs, so binding a key that your keymap does not provide will panic at startup.
+/// The keymap is read using `xmodmap -pke` by default: if your keys are configured through xkb
+/// and are missing from that output then set `PENROSE_KEYMAP_SOURCE=xkbcomp` when running penrose
+/// to read the xkb keymap instead (see [keycodes_from_keymap][crate::core::helpers::keycodes_from_keymap]).
+/// If you need to bind keys that are not in that set you can set
+/// `PENROSE_SKIP_KEY_VALIDATION=1` when compiling to skip the key name check. Modifiers and duplicate bindings are still validated.
+///
+/// To validate against a specific keyboard layout instead (for example in CI where there is no X
+/// server to run xmodmap against) capture the output of `xmodmap -pke` to a file and set
+/// `PENROSE_XMODMAP_DUMP=/path/to/dump` when compiling. Key names are then checked against the
+/// keys listed in that file.
+///
+/// Bindings that are commonly intercepted before they reach the window manager (`C-A-F1` to
+/// `C-A-F12` for switching virtual terminals, `C-A-BackSpace`, `C-A-Delete` and `A-Print`) are
+/// reported as compiler warnings. Set `PENROSE_SYSTEM_SHORTCUTS` to a comma separated list of
+/// bindings to check against instead (an empty value disables the check) or set
+/// `PENROSE_DENY_SYSTEM_SHORTCUTS=1` to make them an error.
+///
+/// If a binding is not being validated the way you expect then setting `PENROSE_DEBUG_BINDINGS=1`
+/// prints each of the bindings that the macro has parsed (after expanding `map` blocks and
+/// modifier groups) as part of the compiler output.
+///
+/// Note that cargo does not track changes to these environment variables or the dump file, so you
+/// may need to `cargo clean` after changing them.
+///
 /// [1]: crate::core::bindings::KeyEventHandler
 /// [2]: crate::core::manager::WindowManager
+/// [3]: crate::core::bindings::ModifierMap
+/// [4]: crate::core::bindings::expand_modifier_aliases
+/// [5]: crate::core::bindings::KeyChord
+/// [6]: crate::xcb::helpers::parse_key_chord
 #[macro_export]
 macro_rules! gen_keybindings {
+    {
+        modifier_aliases: { $($alias:expr => $expansion:expr),+ $(,)? };
+        $($tokens:tt)*
+    } => {
+        {
+            let mut map = ::std::collections::HashMap::new();
+            let codes = $crate::core::helpers::keycodes_from_keymap();
+            let parse = $crate::xcb::helpers::parse_key_binding;
+            __private!(@parsekey map, codes, parse, [], [], [], [ $(($alias, $expansion),)+ ],
+                $($tokens)*
+            );
+            map
+        }
+    };
+
     { $($tokens:tt)* } => {
         {
             let mut map = ::std::collections::HashMap::new();
-            let codes = $crate::core::helpers::keycodes_from_xmodmap();
+            let codes = $crate::core::helpers::keycodes_from_keymap();
             let parse = $crate::xcb::helpers::parse_key_binding;
-            __private!(@parsekey map, codes, parse, [], [], $($tokens)*);
+            __private!(@parsekey map, codes, parse, [], [], [], [], $($tokens)*);
             map
         }
     };
 }
 
+/// Pair validated key bindings with values of your own choosing (typically variants of an action
+/// enum) rather than [KeyEventHandler][1]s.
+///
+/// Bindings use the same syntax and are validated at compile time in the same way as
+/// [gen_keybindings], including skipping bindings prefixed with `#`. The result is a
+/// `Vec<(KeyCode, T)>` in the order the bindings were given, so the pairing between each key
+/// and its action is written out once instead of being kept in sync between two lists.
+///
+/// Modifier groups such as `"M-[S,C]-j"` and key groups such as `"M-(Return|KP_Enter)"` produce
+/// one entry for each of the bindings they expand to, each paired with a clone of the action, so
+/// actions need to implement `Clone`.
+///
+/// ```no_run
+/// # #[macro_use] extern crate penrose;
+/// # use penrose::core::bindings::KeyCode;
+/// #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// enum Action {
+///     FocusNext,
+///     FocusPrevious,
+///     Launch(&'static str),
+/// }
+///
+/// let bindings: Vec<(KeyCode, Action)> = gen_action_bindings! {
+///     "M-j" => Action::FocusNext;
+///     "M-k" => Action::FocusPrevious;
+///     "M-Return" => Action::Launch("alacritty");
+/// };
+/// ```
+///
+/// [1]: crate::core::bindings::KeyEventHandler
+#[macro_export]
+macro_rules! gen_action_bindings {
+    { $($binding:expr => $action:expr;)+ } => {
+        {
+            let codes = $crate::core::helpers::keycodes_from_keymap();
+            let mut bindings = ::std::vec::Vec::new();
+
+            $(
+                if !$binding.trim_start().starts_with($crate::core::bindings::DISABLED_PREFIX) {
+                    let action = $action;
+                    for binding in $crate::core::bindings::expand_modifier_groups($binding) {
+                        match $crate::xcb::helpers::parse_key_binding(binding.clone(), &codes) {
+                            None => panic!("invalid key binding: {}", binding),
+                            Some(key_code) => {
+                                bindings.push((key_code, ::std::clone::Clone::clone(&action)))
+                            }
+                        };
+                    }
+                }
+            )+
+
+            validate_user_bindings!(( $($binding),+ ) ());
+            bindings
+        }
+    };
+}
+
 /// Make creating all of the mouse bindings less verbose
+///
+/// Bindings can either be given as a [MouseButton][1] and a list of [ModifierKey][2]s or using the
+/// same string syntax as [gen_keybindings], with the button given as one of `Button1` to
+/// `Button5`. String bindings are validated at compile time in the same way as key bindings.
+///
+/// ```no_run
+/// # #[macro_use] extern crate penrose;
+/// # use penrose::__example_helpers::*;
+/// # use penrose::core::bindings::MouseEvent;
+/// # fn example() -> ExampleMouseBindings {
+/// let by_name = gen_mousebindings! {
+///     Press Right + [Meta] => |wm: &mut ExampleWM, _: &MouseEvent| wm.cycle_workspace(Forward),
+///     Press Left + [Meta] => |wm: &mut ExampleWM, _: &MouseEvent| wm.cycle_workspace(Backward)
+/// };
+///
+/// let by_pattern = gen_mousebindings! {
+///     Press "M-Button3" => |wm: &mut ExampleWM, _: &MouseEvent| wm.cycle_workspace(Forward),
+///     Press "M-Button1" => |wm: &mut ExampleWM, _: &MouseEvent| wm.cycle_workspace(Backward)
+/// };
+/// # by_pattern }
+/// ```
+///
+/// [1]: crate::core::bindings::MouseButton
+/// [2]: crate::core::bindings::ModifierKey
 #[macro_export]
 macro_rules! gen_mousebindings {
     {
@@ -235,6 +533,31 @@ macro_rules! gen_mousebindings {
             _map
         }
     };
+
+    {
+        $($kind:ident $binding:literal => $action:expr),+
+    } => {
+        {
+            // HashMap<(MouseEventKind, MouseState), MouseEventHandler>
+            let mut _map = ::std::collections::HashMap::new();
+
+            $(
+                let state = match $crate::core::bindings::parse_mouse_binding($binding) {
+                    Ok(state) => state,
+                    Err(e) => panic!("invalid mouse binding: {}", e),
+                };
+
+                let kind = $crate::core::bindings::MouseEventKind::$kind;
+                _map.insert(
+                    (kind, state),
+                    Box::new($action) as $crate::core::bindings::MouseEventHandler<_>
+                );
+            )+
+
+            validate_user_mouse_bindings!($($binding),+);
+            _map
+        }
+    };
 }
 
 /// Quickly create a simple string error
@@ -460,18 +783,35 @@ macro_rules! __private {
      */
 
     {   @parsekey $map:expr, $codes:expr, $parse:expr,
-        [ $($patt:expr,)* ], [ $(($($template:expr),+; $($name:expr),+)),* ],
-        map: { $($str:expr),+ } to $to:expr => {
+        [ $($patt:expr,)* ], [ $(($($template:expr),+; $(($($name:expr),+))+)),* ], [ $($modifier:expr,)* ],
+        [ $(($alias:expr, $expansion:expr),)* ],
+        map: { $($str:expr),+ $(,)? } $({ $($more:expr),+ $(,)? })* to $to:expr => {
             $( $binding:expr => $method:ident ( $($params:tt)* ); )+
         };
         $($tail:tt)*
     } => {
         {
-            let keynames = &[$($str),+];
+            let keynames: Vec<Vec<String>> = vec![
+                vec![$($crate::core::bindings::IntoKeyNames::into_key_names($str)),+].concat(),
+                $(vec![$($crate::core::bindings::IntoKeyNames::into_key_names($more)),+].concat(),)*
+            ];
+            let keynames: Vec<Vec<&str>> = keynames
+                .iter()
+                .map(|names| names.iter().map(AsRef::as_ref).collect())
+                .collect();
+            let keynames: Vec<&[&str]> = keynames.iter().map(AsRef::as_ref).collect();
+            let aliases: &[(&str, &str)] = &[ $(($alias, $expansion)),* ];
             $(
-                for (name, arg) in keynames.iter().zip($to.into_iter()) {
-                    let binding = format!($binding, name);
-                    match $parse(binding.clone(), &$codes) {
+                let bindings = match $crate::core::helpers::expand_template($binding, &keynames) {
+                    Ok(bindings) => bindings,
+                    Err(e) => panic!("{}", e),
+                };
+                for (binding, arg) in bindings.into_iter().zip($to.into_iter()) {
+                    if binding.trim_start().starts_with($crate::core::bindings::DISABLED_PREFIX) {
+                        continue;
+                    }
+                    let expanded = $crate::core::bindings::expand_modifier_aliases(&binding, aliases);
+                    match $parse(expanded, &$codes) {
                         None => panic!("invalid key binding: {}", binding),
                         Some(key_code) => $map.insert(
                             key_code,
@@ -485,31 +825,78 @@ macro_rules! __private {
             )+
 
             __private!(@parsekey $map, $codes, $parse,
-                [ $($patt,)* ], [ $(($($template),+; $($name),+),)* ($($binding),+; $($str),+) ],
+                [ $($patt,)* ], [
+                    $(($($template),+; $(($($name),+))+),)*
+                    ($($binding),+; ($($str),+) $(($($more),+))*)
+                ],
+                [ $($modifier,)* ], [ $(($alias, $expansion),)* ],
                 $($tail)*
             );
         }
     };
 
+    // enable additional modifiers for the remaining bindings
+    {   @parsekey $map:expr, $codes:expr, $parse:expr,
+        [ $($patt:expr,)* ], [ $(($($template:expr),+; $(($($name:expr),+))+)),* ], [ $($modifier:expr,)* ],
+        [ $(($alias:expr, $expansion:expr),)* ],
+        extra_modifiers: { $($extra:expr),+ $(,)? };
+        $($tail:tt)*
+    } => {
+        __private!(@parsekey $map, $codes, $parse,
+            [ $($patt,)* ], [ $(($($template),+; $(($($name),+))+)),* ], [ $($modifier,)* $($extra,)+ ],
+            [ $(($alias, $expansion),)* ],
+            $($tail)*
+        );
+    };
+
+    // parse the remaining bindings using a custom modifier map
+    {   @parsekey $map:expr, $codes:expr, $parse:expr,
+        [ $($patt:expr,)* ], [ $(($($template:expr),+; $(($($name:expr),+))+)),* ], [ $($modifier:expr,)* ],
+        [ $(($alias:expr, $expansion:expr),)* ],
+        modifier_map: $modifier_map:expr;
+        $($tail:tt)*
+    } => {
+        let modifier_map: $crate::core::bindings::ModifierMap = $modifier_map;
+        let parse = |pattern, codes: &_| {
+            $crate::xcb::helpers::parse_key_binding_with_modifiers(pattern, codes, &modifier_map)
+        };
+        __private!(@parsekey $map, $codes, parse,
+            [ $($patt,)* ], [ $(($($template),+; $(($($name),+))+)),* ], [ $($modifier,)* ],
+            [ $(($alias, $expansion),)* ],
+            $($tail)*
+        );
+    };
+
     // parse a single simple key binding (validated if $validate is true)
     {   @parsekey $map:expr, $codes:expr, $parse:expr,
-        [ $($patt:expr,)* ], [ $(($($template:expr),+; $($name:expr),+)),* ],
+        [ $($patt:expr,)* ], [ $(($($template:expr),+; $(($($name:expr),+))+)),* ], [ $($modifier:expr,)* ],
+        [ $(($alias:expr, $expansion:expr),)* ],
         $binding:expr => $action:expr;
         $($tail:tt)*
     } => {
-        match $parse($binding.to_string(), &$codes) {
-            None => panic!("invalid key binding: {}", $binding),
-            Some(key_code) => $map.insert(key_code, $action),
-        };
+        if !$binding.trim_start().starts_with($crate::core::bindings::DISABLED_PREFIX) {
+            let bindings = $crate::core::bindings::expand_modifier_groups($binding);
+            let handlers = $crate::core::bindings::share_key_handler($action, bindings.len());
+            let aliases: &[(&str, &str)] = &[ $(($alias, $expansion)),* ];
+            for (binding, handler) in bindings.into_iter().zip(handlers) {
+                let expanded = $crate::core::bindings::expand_modifier_aliases(&binding, aliases);
+                match $parse(expanded, &$codes) {
+                    None => panic!("invalid key binding: {}", binding),
+                    Some(key_code) => $map.insert(key_code, handler),
+                };
+            }
+        }
         __private!(@parsekey $map, $codes, $parse,
-            [ $binding, $($patt,)* ], [ $(($($template),+; $($name),+)),* ],
+            [ $binding, $($patt,)* ], [ $(($($template),+; $(($($name),+))+)),* ], [ $($modifier,)* ],
+            [ $(($alias, $expansion),)* ],
             $($tail)*
         );
     };
 
     // TODO: remove this target in 0.2.2
     {   @parsekey $map:expr, $codes:expr, $parse:expr,
-        [ $($patt:expr,)* ], [ $(($($template:expr),+; $($name:expr),+)),* ],
+        [ $($patt:expr,)* ], [ $(($($template:expr),+; $(($($name:expr),+))+)),* ], [ $($modifier:expr,)* ],
+        [ $(($alias:expr, $expansion:expr),)* ],
         $(refmap)? $(map)? [ $from:expr ] in { $($binding:expr => $method:ident [ $to:expr ];)+ };
         $($tail:tt)*
     } => {
@@ -522,13 +909,16 @@ macro_rules! __private {
 
     // base case (should be out of tokens)
     {   @parsekey $map:expr, $codes:expr, $parse:expr,
-        [ $($patt:expr,)* ], [ $(($($template:expr),+; $($name:expr),+)),* ],
+        [ $($patt:expr,)* ], [ $(($($template:expr),+; $(($($name:expr),+))+)),* ], [ $($modifier:expr,)* ],
+        [ $(($alias:expr, $expansion:expr),)* ],
         $($tail:tt)*
     } => {
         $(compile_error!(stringify!("unexpected tokens in gen_keybindings macro: " $tail));)*
         validate_user_bindings!(
             ( $($patt),* )
-            ( $((($($template),+) ($($name),+)))* )
+            ( $((($($template),+) $(($($name),+))+))* )
+            ( $($modifier),* )
+            ( $(($alias, $expansion)),* )
         )
     };
 
@@ -49,6 +49,10 @@ pub enum EventAction {
     MoveClientIfFloating(Xid, Region),
     /// A grabbed keybinding was triggered
     RunKeyBinding(KeyCode),
+    /// A grabbed keybinding was triggered by an autorepeat from its keys being held down
+    RunRepeatedKeyBinding(KeyCode),
+    /// A grabbed key was released
+    KeyReleased(KeyCode),
     /// A grabbed mouse state was triggered
     RunMouseBinding(MouseEvent),
     /// The active client should be set to this id
@@ -75,6 +79,8 @@ where
         XEvent::Expose(_) => vec![], // FIXME: work out if this needs handling in the WindowManager
         XEvent::FocusIn(id) => vec![EventAction::FocusIn(id)],
         XEvent::KeyPress(code) => vec![EventAction::RunKeyBinding(code)],
+        XEvent::KeyRepeat(code) => vec![EventAction::RunRepeatedKeyBinding(code)],
+        XEvent::KeyRelease(code) => vec![EventAction::KeyReleased(code)],
         XEvent::Leave(p) => vec![
             EventAction::ClientFocusLost(p.id),
             EventAction::SetScreenFromPoint(Some(p.abs)),
@@ -20,7 +20,12 @@ use crate::core::{helpers::logging_error_handler, layout::LayoutFunc};
 use nix::sys::signal::{signal, SigHandler, Signal};
 use tracing::Level;
 
-use std::{cell::Cell, collections::HashMap, fmt};
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    fmt,
+    time::{Duration, Instant},
+};
 
 mod event;
 mod util;
@@ -88,6 +93,11 @@ pub struct WindowManager<X: XConn> {
     error_handler: ErrorHandler,
     #[cfg_attr(feature = "serde", serde(skip))]
     hydrated: bool,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    key_repeat: bool,
+    // A modifier key with a tap binding that has been pressed but not yet released
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pending_tap: Option<(KeyCode, Instant)>,
 }
 
 impl<X: XConn> fmt::Debug for WindowManager<X> {
@@ -155,6 +165,8 @@ impl<X: XConn> WindowManager<X> {
             focused_client: None,
             running: false,
             hydrated: true,
+            key_repeat: false,
+            pending_tap: None,
             error_handler,
         }
     }
@@ -306,8 +318,13 @@ impl<X: XConn> WindowManager<X> {
             EventAction::FocusIn(id) => self.focus_in(id)?,
             EventAction::MapWindow(id) => self.handle_map_request(id)?,
             EventAction::MoveClientIfFloating(id, r) => self.handle_move_if_floating(id, r)?,
-            EventAction::RunKeyBinding(k) => self.run_key_binding(k, key_bindings),
-            EventAction::RunMouseBinding(e) => self.run_mouse_binding(e, mouse_bindings),
+            EventAction::KeyReleased(k) => self.handle_key_release(k, key_bindings),
+            EventAction::RunKeyBinding(k) => self.handle_key_press(k, false, key_bindings),
+            EventAction::RunRepeatedKeyBinding(k) => self.handle_key_press(k, true, key_bindings),
+            EventAction::RunMouseBinding(e) => {
+                self.pending_tap = None;
+                self.run_mouse_binding(e, mouse_bindings)
+            }
             EventAction::SetActiveClient(id) => self.set_active_client(id)?,
             EventAction::SetActiveWorkspace(wix) => self.focus_workspace(&Selector::Index(wix))?,
             EventAction::SetScreenFromPoint(p) => self.set_screen_from_point(p)?,
@@ -734,16 +751,47 @@ impl<X: XConn> WindowManager<X> {
         Ok(self.conn.set_client_state(id, WindowState::Withdrawn)?)
     }
 
+    // A press of a modifier key with a tap binding starts a potential tap which is cancelled by
+    // any other key press (i.e. the modifier being used as a modifier) or a mouse binding.
+    // Autorepeats of the modifier itself are ignored while it is held.
+    fn handle_key_press(&mut self, k: KeyCode, repeat: bool, bindings: &mut KeyBindings<X>) {
+        let tap = k.as_tap();
+        match self.pending_tap {
+            Some((pending, _)) if repeat && pending == tap => return,
+            _ => self.pending_tap = None,
+        }
+
+        if !repeat && k.ignoring_locks().mask() == 0 && bindings.contains_key(&tap) {
+            trace!(code = k.code(), "possible modifier tap");
+            self.pending_tap = Some((tap, Instant::now()));
+        } else {
+            self.run_key_binding(k, repeat, bindings);
+        }
+    }
+
+    fn handle_key_release(&mut self, k: KeyCode, bindings: &mut KeyBindings<X>) {
+        let timeout = Duration::from_millis(self.config.tap_timeout_ms);
+        match self.pending_tap.take() {
+            Some((tap, pressed)) if tap.code() == k.code() && pressed.elapsed() <= timeout => {
+                self.run_key_binding(tap, false, bindings)
+            }
+            _ => (),
+        }
+    }
+
     // NOTE: This defers control of the [WindowManager] to the user's key-binding action
     //       which can lead to arbitrary calls to public methods on the [WindowManager]
     //       including mutable methods.
     #[tracing::instrument(level = "debug", skip(self, k, bindings), fields(k.code, k.mask))]
-    fn run_key_binding(&mut self, k: KeyCode, bindings: &mut KeyBindings<X>) {
-        if let Some(action) = bindings.get_mut(&k) {
+    fn run_key_binding(&mut self, k: KeyCode, repeat: bool, bindings: &mut KeyBindings<X>) {
+        let bound = k.binding_candidates().find(|c| bindings.contains_key(c));
+        if let Some(action) = bound.and_then(|c| bindings.get_mut(&c)) {
+            self.key_repeat = repeat;
             // ignoring Child handlers and SIGCHILD
             if let Err(e) = action(self) {
                 (self.error_handler)(e);
             }
+            self.key_repeat = false;
         }
     }
 
@@ -918,6 +966,17 @@ impl<X: XConn> WindowManager<X> {
         &self.conn
     }
 
+    /// Whether the key binding currently being run was triggered by the X server autorepeating
+    /// a held key rather than by the initial key press.
+    ///
+    /// This is always false outside of a key binding. See [no_repeat][1] for running a binding
+    /// only on the initial key press.
+    ///
+    /// [1]: crate::core::bindings::no_repeat
+    pub fn key_press_is_repeat(&self) -> bool {
+        self.key_repeat
+    }
+
     /// Get an mutable reference to the underlying [XConn] impl that backs this [WindowManager]
     ///
     /// # A word of warning
@@ -2341,6 +2400,7 @@ mod tests {
     use crate::{
         __example_helpers::{RecordedCall, RecordingXConn},
         core::{
+            bindings::KeyEventHandler,
             data_types::*,
             helpers::logging_error_handler,
             layout::*,
@@ -2351,7 +2411,7 @@ mod tests {
         draw::Color,
     };
 
-    use std::{cell::Cell, convert::TryFrom};
+    use std::{cell::Cell, convert::TryFrom, rc::Rc};
 
     fn wm_with_mock_conn(events: Vec<XEvent>, unmanaged_ids: Vec<Xid>) -> WindowManager<MockXConn> {
         let conn = MockXConn::new(test_screens(), events, unmanaged_ids);
@@ -2386,6 +2446,150 @@ mod tests {
         }
     }
 
+    #[test]
+    fn no_repeat_bindings_only_run_on_the_initial_key_press() {
+        use crate::core::bindings::no_repeat;
+
+        let mut wm = wm_with_mock_conn(vec![], vec![]);
+        let (once, always) = (KeyCode::from_raw(0, 1), KeyCode::from_raw(0, 2));
+        let (n_once, n_always) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+        let counter = |n: &Rc<Cell<usize>>| -> KeyEventHandler<MockXConn> {
+            let n = Rc::clone(n);
+            Box::new(move |_: &mut WindowManager<MockXConn>| {
+                n.set(n.get() + 1);
+                Ok(())
+            })
+        };
+
+        let mut key_bindings: KeyBindings<MockXConn> = map! {
+            once => no_repeat(counter(&n_once)),
+            always => counter(&n_always),
+        };
+        let mut mouse_bindings = HashMap::new();
+
+        for &k in &[once, always] {
+            for action in &[
+                EventAction::RunKeyBinding(k),
+                EventAction::RunRepeatedKeyBinding(k),
+                EventAction::RunRepeatedKeyBinding(k),
+            ] {
+                wm.handle_event_action(action.clone(), &mut key_bindings, &mut mouse_bindings)
+                    .unwrap();
+            }
+        }
+
+        assert_eq!(n_once.get(), 1);
+        assert_eq!(n_always.get(), 3);
+        assert!(!wm.key_press_is_repeat());
+    }
+
+    fn tap_test_bindings(n: &Rc<Cell<usize>>) -> KeyBindings<MockXConn> {
+        let n = Rc::clone(n);
+        let action: KeyEventHandler<MockXConn> =
+            Box::new(move |_: &mut WindowManager<MockXConn>| {
+                n.set(n.get() + 1);
+                Ok(())
+            });
+
+        map! { KeyCode::from_raw(0, 1).as_tap() => action, }
+    }
+
+    #[test]
+    fn tap_bindings_run_when_the_modifier_is_pressed_on_its_own() {
+        let mut wm = wm_with_mock_conn(vec![], vec![]);
+        let n = Rc::new(Cell::new(0));
+        let mut key_bindings = tap_test_bindings(&n);
+        let mut mouse_bindings = HashMap::new();
+        let k = KeyCode::from_raw(0, 1);
+
+        for action in &[
+            EventAction::RunKeyBinding(k),
+            EventAction::RunRepeatedKeyBinding(k),
+            EventAction::KeyReleased(KeyCode::from_raw(64, 1)),
+        ] {
+            wm.handle_event_action(action.clone(), &mut key_bindings, &mut mouse_bindings)
+                .unwrap();
+        }
+
+        assert_eq!(n.get(), 1);
+    }
+
+    #[test]
+    fn tap_bindings_are_cancelled_by_other_key_presses() {
+        let mut wm = wm_with_mock_conn(vec![], vec![]);
+        let n = Rc::new(Cell::new(0));
+        let mut key_bindings = tap_test_bindings(&n);
+        let mut mouse_bindings = HashMap::new();
+        let k = KeyCode::from_raw(0, 1);
+
+        for action in &[
+            EventAction::RunKeyBinding(k),
+            EventAction::RunKeyBinding(KeyCode::from_raw(64, 2)),
+            EventAction::KeyReleased(KeyCode::from_raw(64, 2)),
+            EventAction::KeyReleased(KeyCode::from_raw(64, 1)),
+        ] {
+            wm.handle_event_action(action.clone(), &mut key_bindings, &mut mouse_bindings)
+                .unwrap();
+        }
+
+        assert_eq!(n.get(), 0);
+    }
+
+    #[test]
+    fn bindings_with_explicit_lock_modifiers_are_run() {
+        use crate::core::bindings::{CAPS_LOCK_MASK, NUM_LOCK_MASK};
+
+        let mut wm = wm_with_mock_conn(vec![], vec![]);
+        let (n_caps, n_plain) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+        let counter = |n: &Rc<Cell<usize>>| -> KeyEventHandler<MockXConn> {
+            let n = Rc::clone(n);
+            Box::new(move |_: &mut WindowManager<MockXConn>| {
+                n.set(n.get() + 1);
+                Ok(())
+            })
+        };
+
+        let mut key_bindings: KeyBindings<MockXConn> = map! {
+            KeyCode::from_raw(64 | CAPS_LOCK_MASK, 1) => counter(&n_caps),
+            KeyCode::from_raw(64, 1) => counter(&n_plain),
+        };
+        let mut mouse_bindings = HashMap::new();
+
+        for &mask in &[
+            64 | CAPS_LOCK_MASK,
+            64 | CAPS_LOCK_MASK | NUM_LOCK_MASK,
+            64,
+            64 | NUM_LOCK_MASK,
+        ] {
+            let action = EventAction::RunKeyBinding(KeyCode::from_raw(mask, 1));
+            wm.handle_event_action(action, &mut key_bindings, &mut mouse_bindings)
+                .unwrap();
+        }
+
+        assert_eq!(n_caps.get(), 2);
+        assert_eq!(n_plain.get(), 2);
+    }
+
+    #[test]
+    fn tap_bindings_run_with_lock_modifiers_active() {
+        use crate::core::bindings::NUM_LOCK_MASK;
+
+        let mut wm = wm_with_mock_conn(vec![], vec![]);
+        let n = Rc::new(Cell::new(0));
+        let mut key_bindings = tap_test_bindings(&n);
+        let mut mouse_bindings = HashMap::new();
+
+        for action in &[
+            EventAction::RunKeyBinding(KeyCode::from_raw(NUM_LOCK_MASK, 1)),
+            EventAction::KeyReleased(KeyCode::from_raw(64 | NUM_LOCK_MASK, 1)),
+        ] {
+            wm.handle_event_action(action.clone(), &mut key_bindings, &mut mouse_bindings)
+                .unwrap();
+        }
+
+        assert_eq!(n.get(), 1);
+    }
+
     #[test]
     fn workspace_switching_with_active_clients() {
         let mut wm = wm_with_mock_conn(vec![], vec![]);
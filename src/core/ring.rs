@@ -1,13 +1,14 @@
 //! An internal data structure and associated helpers for simplifying actions around
 //! manipulating focusable ordered collections.
 
-use crate::core::xconnection::Xid;
+use crate::{core::xconnection::Xid, PenroseError};
 
 use std::{
     collections::VecDeque,
     fmt,
     iter::{FromIterator, IntoIterator},
     ops::{Index, IndexMut},
+    str::FromStr,
 };
 
 /// A direction to permute a Ring
@@ -30,6 +31,191 @@ impl Direction {
     }
 }
 
+/// Parse a [Direction] from a user provided string (such as a config file), ignoring case.
+///
+/// `forward` and `next` give [Direction::Forward] while `backward`, `back` and `prev` give
+/// [Direction::Backward].
+///
+/// ```
+/// # use penrose::core::ring::Direction;
+/// assert_eq!("Next".parse::<Direction>().unwrap(), Direction::Forward);
+/// assert_eq!("back".parse::<Direction>().unwrap(), Direction::Backward);
+/// assert!("sideways".parse::<Direction>().is_err());
+/// ```
+impl FromStr for Direction {
+    type Err = PenroseError;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        match s.trim().to_ascii_lowercase().as_ref() {
+            "forward" | "next" => Ok(Direction::Forward),
+            "backward" | "back" | "prev" => Ok(Direction::Backward),
+            _ => Err(PenroseError::UnknownDirection(s.into())),
+        }
+    }
+}
+
+/// Displays as `forward` or `backward` so that a [Direction] round trips through [FromStr].
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::Forward => write!(f, "forward"),
+            Direction::Backward => write!(f, "backward"),
+        }
+    }
+}
+
+/// A direction to move within a two dimensional grid
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Direction2D {
+    /// move to the previous row
+    Up,
+    /// move to the next row
+    Down,
+    /// move to the previous column
+    Left,
+    /// move to the next column
+    Right,
+}
+
+impl Direction2D {
+    /// Invert this Direction2D
+    pub fn reverse(&self) -> Direction2D {
+        match self {
+            Direction2D::Up => Direction2D::Down,
+            Direction2D::Down => Direction2D::Up,
+            Direction2D::Left => Direction2D::Right,
+            Direction2D::Right => Direction2D::Left,
+        }
+    }
+}
+
+/// Step one position from `ix` in the given [Direction2D] within a grid of `rows` x `cols`
+/// indices laid out row by row.
+///
+/// If `wrap` is true then moving off the edge of the grid wraps around to the other side of the
+/// same row or column, otherwise `None` is returned for moves that would leave the grid. `None`
+/// is also returned if the grid is empty.
+///
+/// ```
+/// # use penrose::core::ring::{cycle_index_2d, Direction2D};
+/// // 0 1 2
+/// // 3 4 5
+/// assert_eq!(cycle_index_2d(1, 2, 3, Direction2D::Down, false), Some(4));
+/// assert_eq!(cycle_index_2d(5, 2, 3, Direction2D::Right, true), Some(3));
+/// assert_eq!(cycle_index_2d(5, 2, 3, Direction2D::Right, false), None);
+/// ```
+pub fn cycle_index_2d(
+    ix: usize,
+    rows: usize,
+    cols: usize,
+    direction: Direction2D,
+    wrap: bool,
+) -> Option<usize> {
+    if rows == 0 || cols == 0 {
+        return None;
+    }
+
+    let ix = ix % (rows * cols);
+    let (row, col) = (ix / cols, ix % cols);
+    let (row, col) = match direction {
+        Direction2D::Up if wrap || row > 0 => (cycle_index(row, rows, Direction::Backward)?, col),
+        Direction2D::Down if wrap || row < rows - 1 => {
+            (cycle_index(row, rows, Direction::Forward)?, col)
+        }
+        Direction2D::Left if wrap || col > 0 => (row, cycle_index(col, cols, Direction::Backward)?),
+        Direction2D::Right if wrap || col < cols - 1 => {
+            (row, cycle_index(col, cols, Direction::Forward)?)
+        }
+        _ => return None,
+    };
+
+    Some(row * cols + col)
+}
+
+/// Step one position from `ix` in the given [Direction], wrapping at the bounds of `0..len`.
+///
+/// Stepping [Forward][Direction::Forward] from the last index wraps to `0` and stepping
+/// [Backward][Direction::Backward] from `0` wraps to the last index. With a single element
+/// both directions return that element's index (`0`). Returns `None` if `len` is zero as there
+/// is nothing to cycle through.
+///
+/// ```
+/// # use penrose::core::ring::{cycle_index, Direction};
+/// assert_eq!(cycle_index(2, 5, Direction::Forward), Some(3));
+/// assert_eq!(cycle_index(4, 5, Direction::Forward), Some(0));
+/// assert_eq!(cycle_index(0, 5, Direction::Backward), Some(4));
+/// assert_eq!(cycle_index(0, 1, Direction::Forward), Some(0));
+/// assert_eq!(cycle_index(0, 0, Direction::Backward), None);
+/// ```
+pub fn cycle_index(ix: usize, len: usize, direction: Direction) -> Option<usize> {
+    cycle_index_by(ix, len, 1, direction)
+}
+
+/// Step `n` positions from `ix` in the given [Direction], wrapping at the bounds of `0..len`.
+///
+/// Returns `None` if `len` is zero as there is nothing to cycle through.
+///
+/// ```
+/// # use penrose::core::ring::{cycle_index_by, Direction};
+/// assert_eq!(cycle_index_by(1, 5, 3, Direction::Forward), Some(4));
+/// assert_eq!(cycle_index_by(1, 5, 3, Direction::Backward), Some(3));
+/// assert_eq!(cycle_index_by(0, 0, 1, Direction::Forward), None);
+/// ```
+pub fn cycle_index_by(ix: usize, len: usize, n: usize, direction: Direction) -> Option<usize> {
+    if len == 0 {
+        return None;
+    }
+
+    let (ix, n) = (ix % len, n % len);
+
+    Some(match direction {
+        Direction::Forward => (ix + n) % len,
+        Direction::Backward => (ix + len - n) % len,
+    })
+}
+
+/// Iterate over every index in `0..len` exactly once, starting from `ix` and moving in the given
+/// [Direction], wrapping at the bounds of `0..len`.
+///
+/// Yields nothing if `len` is zero.
+///
+/// ```
+/// # use penrose::core::ring::{cycle_from, Direction};
+/// assert_eq!(cycle_from(2, 4, Direction::Forward).collect::<Vec<_>>(), vec![2, 3, 0, 1]);
+/// assert_eq!(cycle_from(2, 4, Direction::Backward).collect::<Vec<_>>(), vec![2, 1, 0, 3]);
+/// assert_eq!(cycle_from(0, 0, Direction::Forward).count(), 0);
+/// ```
+pub fn cycle_from(ix: usize, len: usize, direction: Direction) -> impl Iterator<Item = usize> {
+    (0..len).filter_map(move |n| cycle_index_by(ix, len, n, direction))
+}
+
+/// Step from `ix` in the given [Direction] to the next index of `items` that satisfies `pred`,
+/// wrapping at the bounds of `items`.
+///
+/// `ix` itself is only returned if it is the only index satisfying `pred`. Returns `None` if no
+/// element of `items` satisfies `pred`.
+///
+/// ```
+/// # use penrose::core::ring::{cycle_index_where, Direction};
+/// let items = [1, 2, 3, 4, 5];
+/// assert_eq!(cycle_index_where(1, &items, Direction::Forward, |n| n % 2 == 1), Some(2));
+/// assert_eq!(cycle_index_where(0, &items, Direction::Backward, |n| n % 2 == 0), Some(3));
+/// assert_eq!(cycle_index_where(0, &items, Direction::Forward, |&n| n > 5), None);
+/// ```
+pub fn cycle_index_where<T>(
+    ix: usize,
+    items: &[T],
+    direction: Direction,
+    pred: impl Fn(&T) -> bool,
+) -> Option<usize> {
+    let len = items.len();
+
+    (1..=len)
+        .filter_map(|n| cycle_index_by(ix, len, n, direction))
+        .find(|&i| pred(&items[i]))
+}
+
 /// Where a given element should be inserted into a Ring
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
@@ -139,33 +325,17 @@ impl<T> Ring<T> {
         }
     }
 
-    fn next_index(&self, direction: Direction) -> usize {
-        let max = self.elements.len() - 1;
-        match direction {
-            Direction::Forward => {
-                if self.focused == max {
-                    0
-                } else {
-                    self.focused + 1
-                }
-            }
-            Direction::Backward => {
-                if self.focused == 0 {
-                    max
-                } else {
-                    self.focused - 1
-                }
-            }
-        }
+    fn next_index(&self, direction: Direction) -> Option<usize> {
+        cycle_index(self.focused, self.elements.len(), direction)
     }
 
     pub fn cycle_focus(&mut self, direction: Direction) -> Option<&T> {
-        self.focused = self.next_index(direction);
+        self.focused = self.next_index(direction)?;
         self.focused()
     }
 
     pub fn drag_focused(&mut self, direction: Direction) -> Option<&T> {
-        match (self.focused, self.next_index(direction), direction) {
+        match (self.focused, self.next_index(direction)?, direction) {
             (0, _, Direction::Backward) => self.rotate(direction),
             (_, 0, Direction::Forward) => self.rotate(direction),
             (focused, other, _) => self.elements.swap(focused, other),
@@ -427,6 +597,156 @@ impl<'a, T> IntoIterator for &'a mut Ring<T> {
 mod tests {
     use super::*;
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn directions_round_trip_through_serde() {
+        for d in [Direction::Forward, Direction::Backward] {
+            let json = serde_json::to_string(&d).unwrap();
+            assert_eq!(serde_json::from_str::<Direction>(&json).unwrap(), d);
+        }
+    }
+
+    test_cases! {
+        direction_from_str;
+        args: (s: &str, expected: Option<Direction>);
+
+        case: forward => ("forward", Some(Direction::Forward));
+        case: next => ("next", Some(Direction::Forward));
+        case: backward => ("backward", Some(Direction::Backward));
+        case: back => ("back", Some(Direction::Backward));
+        case: prev => ("prev", Some(Direction::Backward));
+        case: case_is_ignored => ("ForWard", Some(Direction::Forward));
+        case: unknown => ("sideways", None);
+        case: empty => ("", None);
+        body: {
+            assert_eq!(s.parse::<Direction>().ok(), expected);
+        }
+    }
+
+    #[test]
+    fn direction_display_round_trips() {
+        for d in [Direction::Forward, Direction::Backward] {
+            assert_eq!(d.to_string().parse::<Direction>().unwrap(), d);
+        }
+    }
+
+    test_cases! {
+        cycle_index;
+        args: (ix: usize, len: usize, direction: Direction, expected: Option<usize>);
+
+        case: forward_mid_range => (2, 5, Direction::Forward, Some(3));
+        case: backward_mid_range => (2, 5, Direction::Backward, Some(1));
+        case: forward_wraps_at_max => (4, 5, Direction::Forward, Some(0));
+        case: backward_wraps_at_zero => (0, 5, Direction::Backward, Some(4));
+        case: forward_single_element => (0, 1, Direction::Forward, Some(0));
+        case: backward_single_element => (0, 1, Direction::Backward, Some(0));
+        case: forward_empty => (0, 0, Direction::Forward, None);
+        case: backward_empty => (0, 0, Direction::Backward, None);
+        body: {
+            assert_eq!(cycle_index(ix, len, direction), expected);
+        }
+    }
+
+    test_cases! {
+        cycle_index_by;
+        args: (ix: usize, len: usize, n: usize, direction: Direction, expected: Option<usize>);
+
+        case: forward_single_step => (1, 5, 1, Direction::Forward, Some(2));
+        case: backward_single_step => (1, 5, 1, Direction::Backward, Some(0));
+        case: forward_wraps => (4, 5, 1, Direction::Forward, Some(0));
+        case: backward_wraps => (0, 5, 1, Direction::Backward, Some(4));
+        case: forward_multiple_steps_wrap => (3, 5, 3, Direction::Forward, Some(1));
+        case: backward_multiple_steps_wrap => (1, 5, 3, Direction::Backward, Some(3));
+        case: steps_larger_than_len => (1, 5, 11, Direction::Forward, Some(2));
+        case: zero_steps => (2, 5, 0, Direction::Backward, Some(2));
+        case: empty_forward => (0, 0, 1, Direction::Forward, None);
+        case: empty_backward => (0, 0, 1, Direction::Backward, None);
+        case: single_element_forward => (0, 1, 1, Direction::Forward, Some(0));
+        case: single_element_backward => (0, 1, 1, Direction::Backward, Some(0));
+        case: out_of_bounds_index => (7, 5, 1, Direction::Backward, Some(1));
+        body: {
+            assert_eq!(cycle_index_by(ix, len, n, direction), expected);
+        }
+    }
+
+    test_cases! {
+        cycle_index_where;
+        args: (ix: usize, items: Vec<u8>, direction: Direction, expected: Option<usize>);
+
+        case: next_match_forward => (0, vec![1, 0, 1, 1], Direction::Forward, Some(2));
+        case: next_match_backward => (3, vec![1, 0, 1, 1], Direction::Backward, Some(2));
+        case: wraps_forward => (2, vec![1, 1, 0, 0], Direction::Forward, Some(0));
+        case: wraps_backward => (1, vec![0, 1, 0, 1], Direction::Backward, Some(3));
+        case: only_current_matches => (1, vec![0, 1, 0], Direction::Forward, Some(1));
+        case: no_matches => (1, vec![0, 0, 0], Direction::Forward, None);
+        case: empty => (0, vec![], Direction::Backward, None);
+        body: {
+            assert_eq!(cycle_index_where(ix, &items, direction, |&n| n == 1), expected);
+        }
+    }
+
+    test_cases! {
+        cycle_from;
+        args: (ix: usize, len: usize, direction: Direction);
+
+        case: forward => (2, 5, Direction::Forward);
+        case: backward => (2, 5, Direction::Backward);
+        case: from_start => (0, 3, Direction::Backward);
+        case: from_end => (2, 3, Direction::Forward);
+        case: single => (0, 1, Direction::Forward);
+        case: out_of_bounds => (7, 5, Direction::Forward);
+        body: {
+            let indices: Vec<usize> = cycle_from(ix, len, direction).collect();
+            let mut distinct = indices.clone();
+            distinct.sort_unstable();
+            distinct.dedup();
+
+            assert_eq!(indices.len(), len);
+            assert_eq!(distinct, (0..len).collect::<Vec<_>>());
+            assert_eq!(indices[0], ix % len);
+            assert_eq!(indices.get(1).copied(), cycle_index(ix, len, direction).filter(|_| len > 1));
+        }
+    }
+
+    // 0 1 2
+    // 3 4 5
+    test_cases! {
+        cycle_index_2d;
+        args: (ix: usize, direction: Direction2D, wrap: bool, expected: Option<usize>);
+
+        case: up => (4, Direction2D::Up, false, Some(1));
+        case: down => (1, Direction2D::Down, false, Some(4));
+        case: left => (4, Direction2D::Left, false, Some(3));
+        case: right => (4, Direction2D::Right, false, Some(5));
+        case: up_at_edge => (1, Direction2D::Up, false, None);
+        case: down_at_edge => (4, Direction2D::Down, false, None);
+        case: left_at_edge => (3, Direction2D::Left, false, None);
+        case: right_at_edge => (5, Direction2D::Right, false, None);
+        case: up_wrapping => (1, Direction2D::Up, true, Some(4));
+        case: down_wrapping => (5, Direction2D::Down, true, Some(2));
+        case: left_wrapping => (3, Direction2D::Left, true, Some(5));
+        case: right_wrapping => (2, Direction2D::Right, true, Some(0));
+        case: out_of_bounds => (7, Direction2D::Right, false, Some(2));
+        body: {
+            assert_eq!(cycle_index_2d(ix, 2, 3, direction, wrap), expected);
+        }
+    }
+
+    #[test]
+    fn cycling_an_empty_grid_does_nothing() {
+        assert_eq!(cycle_index_2d(0, 0, 3, Direction2D::Down, true), None);
+        assert_eq!(cycle_index_2d(0, 2, 0, Direction2D::Left, true), None);
+    }
+
+    #[test]
+    fn cycling_an_empty_ring_does_nothing() {
+        let mut r: Ring<u8> = Ring::new(vec![]);
+
+        assert_eq!(r.cycle_focus(Direction::Forward), None);
+        assert_eq!(r.cycle_focus(Direction::Backward), None);
+        assert_eq!(r.drag_focused(Direction::Forward), None);
+    }
+
     #[test]
     fn rotate_holds_focus_but_permutes_order() {
         let mut r = Ring::new(vec![1, 2, 3]);
@@ -244,7 +244,7 @@ impl Workspace {
     /// assert_eq!(workspace.remove_focused_client(), Some(0));
     /// assert_eq!(workspace.remove_focused_client(), Some(1));
     /// assert_eq!(workspace.remove_focused_client(), None);
-    /// assert_eq!(workspace.client_ids(), vec![]);
+    /// assert!(workspace.client_ids().is_empty());
     /// # Ok(())
     /// # }
     /// # example(example_workspace("example", 2)).unwrap();
@@ -31,6 +31,10 @@ pub enum XEvent {
     Destroy(Xid),
     /// A grabbed key combination has been entered by the user
     KeyPress(KeyCode),
+    /// A grabbed key combination is being held down and has been autorepeated by the X server
+    KeyRepeat(KeyCode),
+    /// A grabbed key has been released
+    KeyRelease(KeyCode),
     /// The mouse pointer has left the current client window
     Leave(PointerChange),
     /// A client window is requesting to be positioned and rendered on the screen.
@@ -58,6 +62,8 @@ impl std::fmt::Display for XEvent {
             XEvent::FocusIn(_) => write!(f, "FocusIn"),
             XEvent::Destroy(_) => write!(f, "Destroy"),
             XEvent::KeyPress(_) => write!(f, "KeyPress"),
+            XEvent::KeyRepeat(_) => write!(f, "KeyRepeat"),
+            XEvent::KeyRelease(_) => write!(f, "KeyRelease"),
             XEvent::Leave(_) => write!(f, "Leave"),
             XEvent::MapRequest(_, _) => write!(f, "MapRequest"),
             XEvent::MouseEvent(_) => write!(f, "MouseEvent"),
@@ -159,7 +159,9 @@ pub mod x11rb;
 pub mod __example_helpers;
 
 #[doc(hidden)]
-pub use penrose_proc::validate_user_bindings;
+pub use penrose_proc::{
+    validate_user_binding_structure, validate_user_bindings, validate_user_mouse_bindings,
+};
 
 // top level re-exports
 #[doc(inline)]
@@ -205,6 +207,12 @@ pub enum PenroseError {
     #[error(transparent)]
     Io(#[from] std::io::Error),
 
+    /// A user key binding could not be parsed
+    ///
+    /// See [KeyBindingError][crate::core::bindings::KeyBindingError] for variants.
+    #[error(transparent)]
+    KeyBinding(#[from] crate::core::bindings::KeyBindingError),
+
     /// Wm(Normal)Hints received from the X server were invalid
     #[error("Invalid window hints property: {0}")]
     InvalidHints(String),
@@ -226,6 +234,14 @@ pub enum PenroseError {
     #[error("unable to get stdout handle for child process: {0}")]
     SpawnProc(String),
 
+    /// An external command could not be started: the full command line and the reason
+    #[error("error spawning '{0}': {1}")]
+    SpawnFailed(String, String),
+
+    /// An external command did not finish within the time it was allowed and was killed
+    #[error("'{0}' did not finish within {1:?}")]
+    SpawnTimeout(String, std::time::Duration),
+
     /// Parsing an [Atom][core::xconnection::Atom] from a str failed.
     ///
     /// This happens when the atom name being requested is not a known atom.
@@ -236,6 +252,10 @@ pub enum PenroseError {
     #[error("{0} is not a known client")]
     UnknownClient(Xid),
 
+    /// A user specified direction was not one of the known [Direction][crate::core::ring::Direction]s
+    #[error("Unknown direction: {0}")]
+    UnknownDirection(String),
+
     /// A user specified key binding contained an invalid modifier key
     #[error("Unknown modifier key: {0}")]
     UnknownModifier(String),
@@ -257,6 +277,12 @@ pub enum PenroseError {
     /// Something went wrong when communicating with the X server
     #[error(transparent)]
     X(#[from] crate::core::xconnection::XError),
+
+    /// The system keymap could not be read using xmodmap
+    ///
+    /// See [XmodmapError][crate::core::helpers::XmodmapError] for variants.
+    #[error(transparent)]
+    Xmodmap(#[from] crate::core::helpers::XmodmapError),
 }
 
 /// Top level penrose Result type
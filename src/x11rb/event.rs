@@ -15,7 +15,7 @@ use strum::IntoEnumIterator;
 use x11rb::{
     connection::Connection,
     protocol::{
-        xproto::{ClientMessageEvent, ConnectionExt as _, ModMask},
+        xproto::{ClientMessageEvent, ConnectionExt as _},
         Event,
     },
 };
@@ -62,14 +62,17 @@ pub(crate) fn convert_event<C: Connection>(
             ))
         })),
         Event::KeyPress(event) => {
-            let code = KeyCode {
-                mask: event.state,
-                code: event.detail,
-            };
-            let numlock = ModMask::M2;
-            Ok(Some(XEvent::KeyPress(
-                code.ignoring_modifier(numlock.into()),
-            )))
+            let code = KeyCode::from_raw(event.state, event.detail).normalised_keeping_locks();
+            if conn.key_pressed(event.detail, event.time) {
+                Ok(Some(XEvent::KeyRepeat(code)))
+            } else {
+                Ok(Some(XEvent::KeyPress(code)))
+            }
+        }
+        Event::KeyRelease(event) => {
+            conn.key_released(event.detail, event.time);
+            let code = KeyCode::from_raw(event.state, event.detail);
+            Ok(Some(XEvent::KeyRelease(code.normalised_keeping_locks())))
         }
         Event::MapRequest(event) => {
             let attr = conn
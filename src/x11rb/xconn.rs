@@ -9,7 +9,10 @@
 
 use crate::{
     core::{
-        bindings::{KeyBindings, KeyCode, MouseBindings, MouseState},
+        bindings::{
+            forget_all_grabs, record_grab, KeyBindings, KeyCode, KeyCodeValue, KeyRepeatTracker,
+            MouseBindings, MouseState, LOCK_MASK_COMBINATIONS,
+        },
         data_types::{Point, Region},
         screen::Screen,
         xconnection::{
@@ -22,7 +25,7 @@ use crate::{
     x11rb::{atom::Atoms, X11rbError},
 };
 
-use std::{convert::TryFrom, str::FromStr};
+use std::{cell::RefCell, convert::TryFrom, str::FromStr};
 
 use x11rb::{
     connection::Connection,
@@ -48,6 +51,7 @@ pub struct X11rbConnection<C: Connection> {
     root: Xid,
     check_win: Xid,
     atoms: Atoms,
+    key_repeats: RefCell<KeyRepeatTracker>,
 }
 
 impl<C: Connection> X11rbConnection<C> {
@@ -98,9 +102,20 @@ impl<C: Connection> X11rbConnection<C> {
             root,
             check_win,
             atoms,
+            key_repeats: RefCell::new(KeyRepeatTracker::default()),
         })
     }
 
+    /// Record a key press, returning true if it was an autorepeat of a held key
+    pub(super) fn key_pressed(&self, code: KeyCodeValue, time: u32) -> bool {
+        self.key_repeats.borrow_mut().press(code, time)
+    }
+
+    /// Record a key release
+    pub(super) fn key_released(&self, code: KeyCodeValue, time: u32) {
+        self.key_repeats.borrow_mut().release(code, time)
+    }
+
     /// The root window ID
     pub fn root(&self) -> Xid {
         self.root
@@ -523,6 +538,7 @@ impl<C: Connection> XConn for X11rbConnection<C> {
     fn cleanup(&self) -> Result<()> {
         self.conn.ungrab_keyboard(x11rb::CURRENT_TIME)?;
         self.conn.ungrab_key(Grab::ANY, self.root, ModMask::ANY)?;
+        forget_all_grabs(self.root);
         self.conn
             .ungrab_button(ButtonIndex::ANY, self.root, ModMask::ANY)?;
         self.conn.destroy_window(self.check_win)?;
@@ -554,23 +570,26 @@ impl<C: Connection> XConn for X11rbConnection<C> {
 
 impl<C: Connection> X11rbConnection<C> {
     fn grab_key_bindings(&self, keys: &[&KeyCode]) -> Result<()> {
-        // We need to explicitly grab NumLock as an additional modifier and then drop it later on
-        // when we are passing events through to the WindowManager as NumLock alters the modifier
-        // mask when it is active.
-        let modifiers = &[0, u16::from(ModMask::M2)];
+        // We need to explicitly grab NumLock and CapsLock as additional modifiers and then drop
+        // them later on when we are passing events through to the WindowManager as they alter the
+        // modifier mask when they are active.
+        //
+        // Tap bindings are marked using TAP_MASK which is dropped by normalised so that the
+        // modifier key itself is grabbed with no other modifiers held.
         let mode = GrabMode::ASYNC;
 
-        for m in modifiers.iter() {
-            for k in keys.iter() {
+        for k in keys.iter() {
+            for m in k.lock_mask_combinations() {
                 self.conn.grab_key(
-                    false,      // don't pass grabbed events through to the client
-                    self.root,  // the window to grab: in this case the root window
-                    k.mask | m, // modifiers to grab
-                    k.code,     // keycode to grab
-                    mode,       // don't lock pointer input while grabbing
-                    mode,       // don't lock keyboard input while grabbing
+                    false,                     // don't pass grabbed events through to the client
+                    self.root,                 // the window to grab: in this case the root window
+                    k.normalised().mask() | m, // modifiers to grab
+                    k.code(),                  // keycode to grab
+                    mode,                      // don't lock pointer input while grabbing
+                    mode,                      // don't lock keyboard input while grabbing
                 )?;
             }
+            record_grab(self.root, k);
         }
 
         self.flush();
@@ -578,10 +597,10 @@ impl<C: Connection> X11rbConnection<C> {
     }
 
     fn grab_mouse_buttons(&self, states: &[&MouseState]) -> Result<()> {
-        // We need to explicitly grab NumLock as an additional modifier and then drop it later on
-        // when we are passing events through to the WindowManager as NumLock alters the modifier
-        // mask when it is active.
-        let modifiers = &[0, u16::from(ModMask::M2)];
+        // We need to explicitly grab NumLock and CapsLock as additional modifiers and then drop
+        // them later on when we are passing events through to the WindowManager as they alter the
+        // modifier mask when they are active.
+        let modifiers = &LOCK_MASK_COMBINATIONS;
         let mode = GrabMode::ASYNC;
         let mask = EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE | EventMask::BUTTON_MOTION;
         let mask = u16::try_from(u32::from(mask)).unwrap();
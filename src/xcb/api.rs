@@ -1,9 +1,12 @@
 //! A wrapper around the underlying xcb api layer that only exposes Penrose types
 use crate::{
     core::{
-        bindings::{KeyCode, KeyCodeMask, KeyCodeValue, MouseEvent, MouseState},
+        bindings::{
+            forget_all_grabs, KeyCode, KeyCodeMask, KeyCodeValue, KeyRepeatTracker, MouseEvent,
+            MouseState, LOCK_MASK_COMBINATIONS,
+        },
         data_types::{Point, Region, WinType},
-        helpers::spawn_for_output,
+        helpers::{xmodmap_line_levels, xmodmap_output},
         screen::Screen,
         xconnection::{
             Atom, ClientAttr, ClientConfig, ClientEventMask, ClientMessage, ClientMessageData,
@@ -12,11 +15,11 @@ use crate::{
             XAtomQuerier, XEvent, Xid,
         },
     },
-    xcb::{Result, XErrorCode, XcbError, XcbGenericEvent},
+    xcb::{helpers::grab_key, Result, XErrorCode, XcbError, XcbGenericEvent},
 };
 use strum::*;
 
-use std::{collections::HashMap, convert::TryFrom, fmt, str::FromStr};
+use std::{cell::RefCell, collections::HashMap, convert::TryFrom, fmt, str::FromStr};
 
 #[cfg(feature = "keysyms")]
 use crate::core::{bindings::KeyPress, xconnection::KeyPressParseAttempt};
@@ -39,34 +42,29 @@ fn default_conn() -> xcb::Connection {
  * Use `xmodmap -pke` to determine the user's current keymap to allow for mapping X KeySym values
  * to their string representation on the user's system.
  *
- * # Panics
- * This function will panic if it is unable to fetch keycodes using the xmodmap
- * binary on your system or if the output of `xmodmap -pke` is not valid
+ * As with [try_keycodes_from_xmodmap][1], xmodmap is run using the C locale so that the format of
+ * its output does not depend on the locale of the user. Lines of output that do not match the
+ * expected `keycode <code> = <names ...>` format, or that are for key codes above 255, are
+ * skipped.
+ *
+ * [1]: crate::core::helpers::try_keycodes_from_xmodmap
  */
 pub fn code_map_from_xmodmap() -> Result<ReverseCodeMap> {
-    let output = match spawn_for_output("xmodmap -pke") {
-        Ok(s) => s,
-        Err(e) => return Err(XcbError::Raw(e.to_string())), // failed to spawn
-    };
+    let output = xmodmap_output().map_err(|e| XcbError::Raw(e.to_string()))?;
+
     Ok(output
         .lines()
-        .flat_map(|l| {
-            let mut words = l.split_whitespace(); // keycode <code> = <names ...>
-            let key_code: u8 = match words.nth(1) {
-                Some(word) => match word.parse() {
-                    Ok(val) => val,
-                    Err(e) => panic!("{}", e),
-                },
-                None => panic!("unexpected output format from xmodmap -pke"),
-            };
+        .flat_map(xmodmap_line_levels)
+        .flat_map(|(code, levels)| {
+            let mut levels = levels.into_iter();
             vec![
-                words.nth(1).map(move |name| ((0, key_code), name.into())),
-                words.next().map(move |name| ((1, key_code), name.into())),
+                levels.next().map(|name| ((0, code), name)),
+                levels.next().map(|name| ((1, code), name)),
             ]
             .into_iter()
             .flatten()
         })
-        .collect::<HashMap<(u16, u8), String>>())
+        .collect())
 }
 
 /// A connection to the X server using the XCB C API
@@ -79,6 +77,8 @@ pub struct Api {
     atoms: HashMap<Atom, u32>,
     #[cfg(feature = "keysyms")]
     code_map: ReverseCodeMap,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    key_repeats: RefCell<KeyRepeatTracker>,
 }
 
 impl fmt::Debug for Api {
@@ -123,6 +123,7 @@ impl Api {
             atoms: HashMap::new(),
             #[cfg(feature = "keysyms")]
             code_map: code_map_from_xmodmap()?,
+            key_repeats: RefCell::new(KeyRepeatTracker::default()),
         };
         api.init()?;
 
@@ -363,7 +364,7 @@ impl Api {
         event: XcbGenericEvent,
     ) -> Result<Option<KeyPressParseAttempt>> {
         if let Ok(k) = KeyCode::try_from(&event) {
-            if let Some(s) = self.code_map.get(&(k.mask, k.code)) {
+            if let Some(s) = self.code_map.get(&(k.mask(), k.code())) {
                 if let Ok(k) = KeyPress::try_from(XKeySym::from_str(s)?) {
                     return Ok(Some(KeyPressParseAttempt::KeyPress(k)));
                 }
@@ -379,7 +380,6 @@ impl Api {
 
     fn generic_xcb_to_xevent(&self, event: XcbGenericEvent) -> Result<Option<XEvent>> {
         let xcb_response_type_mask: u8 = 0x7F;
-        let numlock = xcb::MOD_MASK_2 as u16;
 
         let etype = event.response_type() & xcb_response_type_mask;
 
@@ -401,9 +401,23 @@ impl Api {
                 }
             }
 
-            xcb::KEY_PRESS => Some(XEvent::KeyPress(
-                KeyCode::try_from(event)?.ignoring_modifier(numlock),
-            )),
+            xcb::KEY_PRESS => {
+                let e: &xcb::KeyPressEvent = unsafe { xcb::cast_event(&event) };
+                let repeat = self.key_repeats.borrow_mut().press(e.detail(), e.time());
+                let code = KeyCode::try_from(event)?.normalised_keeping_locks();
+                if repeat {
+                    Some(XEvent::KeyRepeat(code))
+                } else {
+                    Some(XEvent::KeyPress(code))
+                }
+            }
+
+            xcb::KEY_RELEASE => {
+                let e: &xcb::KeyReleaseEvent = unsafe { xcb::cast_event(&event) };
+                self.key_repeats.borrow_mut().release(e.detail(), e.time());
+                let code = KeyCode::from_raw(e.state(), e.detail());
+                Some(XEvent::KeyRelease(code.normalised_keeping_locks()))
+            }
 
             xcb::MAP_REQUEST => {
                 let e: &xcb::MapRequestEvent = unsafe { xcb::cast_event(&event) };
@@ -878,26 +892,14 @@ impl Api {
 
     /// Register intercepts for each given [KeyCode]
     pub fn grab_keys(&self, keys: &[&KeyCode]) -> Result<()> {
-        // We need to explicitly grab NumLock as an additional modifier and then drop it later on
-        // when we are passing events through to the WindowManager as NumLock alters the modifier
-        // mask when it is active.
-        let modifiers = &[0, xcb::MOD_MASK_2 as u16];
-        let mode = xcb::GRAB_MODE_ASYNC as u8;
-
-        for m in modifiers.iter() {
-            for k in keys.iter() {
-                // xcb docs: https://www.mankier.com/3/xcb_grab_key
-                xcb::grab_key_checked(
-                    &self.conn, // xcb connection to X11
-                    false,      // don't pass grabbed events through to the client
-                    self.root,  // the window to grab: in this case the root window
-                    k.mask | m, // modifiers to grab
-                    k.code,     // keycode to grab
-                    mode,       // don't lock pointer input while grabbing
-                    mode,       // don't lock keyboard input while grabbing
-                )
-                .request_check()?;
-            }
+        // We need to explicitly grab NumLock and CapsLock as additional modifiers and then drop
+        // them later on when we are passing events through to the WindowManager as they alter the
+        // modifier mask when they are active.
+        //
+        // Tap bindings are marked using TAP_MASK which is dropped by normalised so that the
+        // modifier key itself is grabbed with no other modifiers held.
+        for k in keys.iter() {
+            grab_key(&self.conn, self.root, k)?;
         }
 
         self.flush();
@@ -906,10 +908,10 @@ impl Api {
 
     /// Register intercepts for each given [MouseState]
     pub fn grab_mouse_buttons(&self, states: &[&MouseState]) -> Result<()> {
-        // We need to explicitly grab NumLock as an additional modifier and then drop it later on
-        // when we are passing events through to the WindowManager as NumLock alters the modifier
-        // mask when it is active.
-        let modifiers = &[0, xcb::MOD_MASK_2 as u16];
+        // We need to explicitly grab NumLock and CapsLock as additional modifiers and then drop
+        // them later on when we are passing events through to the WindowManager as they alter the
+        // modifier mask when they are active.
+        let modifiers = &LOCK_MASK_COMBINATIONS;
         let mode = xcb::GRAB_MODE_ASYNC as u8;
         let mask = (xcb::EVENT_MASK_BUTTON_PRESS
             | xcb::EVENT_MASK_BUTTON_RELEASE
@@ -977,13 +979,16 @@ impl Api {
 
     /// Drop all active intercepts for key combinations
     pub fn ungrab_keys(&self) -> Result<()> {
-        Ok(xcb::ungrab_key_checked(
+        xcb::ungrab_key_checked(
             &self.conn, // xcb connection to X11
             xcb::GRAB_ANY as u8,
             self.root, // the window to ungrab keys for
             xcb::MOD_MASK_ANY as u16,
         )
-        .request_check()?)
+        .request_check()?;
+
+        forget_all_grabs(self.root);
+        Ok(())
     }
 
     /// Drop all active intercepts for mouse states
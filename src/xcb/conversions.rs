@@ -12,8 +12,13 @@ use strum::IntoEnumIterator;
 use std::convert::TryFrom;
 
 impl ModifierKey {
+    /// The xcb bitmask for this [ModifierKey]
+    pub fn to_mask(&self) -> u16 {
+        u16::from(*self)
+    }
+
     fn was_held(&self, mask: u16) -> bool {
-        mask & u16::from(*self) > 0
+        mask & self.to_mask() > 0
     }
 }
 
@@ -24,25 +29,20 @@ impl From<ModifierKey> for u16 {
             ModifierKey::Alt => xcb::MOD_MASK_1,
             ModifierKey::Shift => xcb::MOD_MASK_SHIFT,
             ModifierKey::Meta => xcb::MOD_MASK_4,
+            ModifierKey::Hyper => xcb::MOD_MASK_3,
         }) as u16
     }
 }
 
 impl From<xcb::KeyPressEvent> for KeyCode {
     fn from(e: xcb::KeyPressEvent) -> Self {
-        Self {
-            mask: e.state(),
-            code: e.detail(),
-        }
+        Self::from_raw(e.state(), e.detail())
     }
 }
 
 impl From<&xcb::KeyPressEvent> for KeyCode {
     fn from(e: &xcb::KeyPressEvent) -> Self {
-        Self {
-            mask: e.state(),
-            code: e.detail(),
-        }
+        Self::from_raw(e.state(), e.detail())
     }
 }
 
@@ -1,5 +1,41 @@
 //! XCB based helper functions
-use crate::core::bindings::{CodeMap, KeyCode};
+use crate::core::{
+    bindings::{
+        expand_modifier_aliases, forget_grab, record_grab, suggest_key_name, CodeMap,
+        KeyBindingError, KeyChord, KeyCode, ModifierKey, ModifierMap, ScrollDirection,
+        BINDING_MODIFIER_MASK,
+    },
+    helpers::{reload_keycode_cache, XmodmapError},
+    xconnection::Xid,
+};
+
+#[cfg(feature = "toml")]
+use crate::core::helpers::try_keycodes_from_keymap;
+
+use std::{collections::BTreeMap, convert::TryFrom};
+
+#[cfg(feature = "toml")]
+use std::path::Path;
+
+use penrose_binding_helpers::{find_key_name, parse_key_code};
+
+pub use crate::core::bindings::grabbed_keys;
+pub use penrose_binding_helpers::{KEY_ALIASES, KEY_CODE_PREFIX, TAP_PREFIX};
+
+// Short form modifier names in the order they are written by format_key_binding along with the
+// key that is bound for 'tap:' bindings of that modifier.
+const MODIFIERS: [(ModifierKey, &str, &str); 5] = [
+    (ModifierKey::Meta, "M", "Super_L"),
+    (ModifierKey::Alt, "A", "Alt_L"),
+    (ModifierKey::Ctrl, "C", "Control_L"),
+    (ModifierKey::Shift, "S", "Shift_L"),
+    (ModifierKey::Hyper, "H", "Hyper_L"),
+];
+
+// Look up the code for a key name, matching it against the known key names using find_key_name
+fn code_for_name(name: &str, known_codes: &CodeMap) -> Option<u8> {
+    find_key_name(name, known_codes.keys().map(String::as_str)).map(|k| known_codes[k])
+}
 
 /**
  * Convert user friendly key bindings into X keycodes.
@@ -9,36 +45,1272 @@ use crate::core::bindings::{CodeMap, KeyCode};
  * with multipple modifiers being allowed, and keynames being taken from the
  * output of 'xmodmap -pke'.
  *
- * Allowed modifiers are:
- *   M - Super
- *   A - Alt
- *   C - Ctrl
- *   S - Shift
+ * Allowed modifiers are (short or long form):
+ *   M / Super - Super
+ *   A / Alt   - Alt
+ *   C / Ctrl  - Ctrl
+ *   S / Shift - Shift
+ *
+ * Short and long forms can be mixed in a single binding: 'Super-S-j'. Whitespace around each
+ * part of the binding is ignored so 'M - j' is the same as 'M-j'. Modifiers are matched ignoring
+ * case ('m-j' is the same as 'M-j'), use [parse_key_binding_strict] to require the exact
+ * spellings listed above.
+ *
+ * Modifiers are matched using the X modifier mask, which does not record whether the left or
+ * right version of a modifier key is being held: 'S-j' fires for both Shift_L and Shift_R. The
+ * individual keys can still be bound directly as key names, e.g. 'M-Shift_R'.
+ *
+ * If you have a key mapped to act as Hyper (mod3) then 'H' / 'Hyper' may also be used.
+ * (This needs to be enabled using 'extra_modifiers' when using gen_keybindings).
+ *
+ * A single modifier prefixed with 'tap:' (e.g. 'tap:M') binds tapping the left hand key for that
+ * modifier on its own, without pressing any other keys while it is held.
+ *
+ * The aliases listed in [KEY_ALIASES] (e.g. 'Space', 'Esc' or 'BrightnessUp') may be used in
+ * place of the keysym names they stand for. Key names that are not in `known_codes` are matched
+ * ignoring case if only one known name matches, so 'M-SPACE' and 'M-return' work as expected
+ * while 'a' and 'A' are still distinct keys.
+ *
+ * Keys that have no name in the current keymap can be bound using their raw X key code (as
+ * reported by 'xev' or 'xmodmap -pke') prefixed with 'code:': 'M-code:191'. The code may be
+ * given in decimal, hex or octal ('code:191', 'code:0xbf' or 'code:0o277') and must be in the
+ * range 0..=255. It is used as given without being looked up in `known_codes`.
  *
  * The user friendly patterns are parsed into a modifier mask and X key code
  * pair that is then grabbed by penrose to trigger the bound action.
  */
 pub fn parse_key_binding(pattern: String, known_codes: &CodeMap) -> Option<KeyCode> {
-    let mut parts: Vec<&str> = pattern.split('-').collect();
-    match known_codes.get(parts.remove(parts.len() - 1)) {
-        Some(code) => {
-            let mask = parts
-                .iter()
-                .map(|&s| match s {
-                    "A" => xcb::MOD_MASK_1,
-                    "M" => xcb::MOD_MASK_4,
-                    "S" => xcb::MOD_MASK_SHIFT,
-                    "C" => xcb::MOD_MASK_CONTROL,
-                    _ => panic!("invalid key binding prefix: {}", s),
-                })
-                .fold(0, |acc, v| acc | v);
-
-            trace!(?pattern, mask, code, "parsed keybinding");
-            Some(KeyCode {
-                mask: mask as u16,
-                code: *code,
-            })
-        }
-        None => None,
+    try_parse_key_binding(pattern, known_codes).ok()
+}
+
+/**
+ * Convert user friendly key bindings into X keycodes, requiring modifiers to be written with the
+ * exact case shown for [parse_key_binding].
+ *
+ * This follows the same rules as [parse_key_binding] in all other respects.
+ */
+pub fn parse_key_binding_strict(pattern: String, known_codes: &CodeMap) -> Option<KeyCode> {
+    parse_binding(pattern, known_codes, true, &ModifierMap::default()).ok()
+}
+
+/**
+ * Convert user friendly key bindings into X keycodes, using `modifiers` to look up the X
+ * modifier mask for each modifier in the binding.
+ *
+ * This follows the same rules as [parse_key_binding] (which uses [ModifierMap::default]) but
+ * allows for setups where the logical modifiers are mapped to different X modifiers, e.g. Super
+ * being on mod3 rather than mod4.
+ */
+pub fn parse_key_binding_with_modifiers(
+    pattern: String,
+    known_codes: &CodeMap,
+    modifiers: &ModifierMap,
+) -> Option<KeyCode> {
+    parse_binding(pattern, known_codes, false, modifiers).ok()
+}
+
+/**
+ * Convert user friendly key bindings into X keycodes, first replacing any modifiers that match
+ * one of `aliases` with the modifiers that they stand for.
+ *
+ * Aliases are expanded using [expand_modifier_aliases] so that a shared prefix can be given a
+ * name once: with `("super_shift", "M-S")` as an alias, 'super_shift-j' is parsed as 'M-S-j'.
+ * This follows the same rules as [parse_key_binding] in all other respects.
+ */
+pub fn parse_key_binding_with_aliases(
+    pattern: String,
+    known_codes: &CodeMap,
+    aliases: &[(&str, &str)],
+) -> Option<KeyCode> {
+    parse_key_binding(expand_modifier_aliases(&pattern, aliases), known_codes)
+}
+
+/**
+ * Convert a list of user friendly key bindings into X keycodes, pairing each pattern with the
+ * result of parsing it using [parse_key_binding].
+ *
+ * Results are returned in the same order as `patterns` so that any bindings which failed to
+ * parse can be reported by name.
+ */
+pub fn parse_key_bindings(
+    patterns: &[String],
+    known_codes: &CodeMap,
+) -> Vec<(String, Option<KeyCode>)> {
+    patterns
+        .iter()
+        .map(|p| (p.clone(), parse_key_binding(p.clone(), known_codes)))
+        .collect()
+}
+
+/**
+ * Convert a group of user friendly key bindings into X keycodes, but only if every key name in
+ * `required_names` is present in `known_codes`.
+ *
+ * This allows for a single config to be used with multiple keyboard layouts: bindings that only
+ * make sense for one keymap can be grouped under the key names that keymap provides and the
+ * group is skipped (returning an empty Vec) on machines where they are missing rather than
+ * failing to load. Within an applicable group, bindings that fail to parse are logged and
+ * skipped.
+ *
+ * ```
+ * # use penrose::xcb::helpers::load_bindings_if;
+ * # use std::collections::HashMap;
+ * let mut codes = HashMap::new();
+ * codes.insert("XF86Launch5".to_string(), 192);
+ *
+ * let bindings = vec![("XF86Launch5".to_string(), "dmenu_run")];
+ * assert_eq!(load_bindings_if(&codes, &["XF86Launch5"], bindings.clone()).len(), 1);
+ * assert!(load_bindings_if(&codes, &["XF86Launch6"], bindings).is_empty());
+ * ```
+ */
+pub fn load_bindings_if<T>(
+    known_codes: &CodeMap,
+    required_names: &[&str],
+    bindings: Vec<(String, T)>,
+) -> Vec<(KeyCode, T)> {
+    if let Some(name) = required_names
+        .iter()
+        .find(|&&name| !known_codes.contains_key(name))
+    {
+        debug!(?name, "required key not found: skipping binding group");
+        return vec![];
+    }
+
+    bindings
+        .into_iter()
+        .filter_map(
+            |(pattern, value)| match try_parse_key_binding(pattern.clone(), known_codes) {
+                Ok(k) => Some((k, value)),
+                Err(e) => {
+                    warn!(?pattern, %e, "skipping invalid key binding");
+                    None
+                }
+            },
+        )
+        .collect()
+}
+
+/**
+ * Convert user friendly key bindings into X keycodes, returning an error if the binding is not
+ * valid.
+ *
+ * This follows the same rules as [parse_key_binding] but reports whether it was the key name or
+ * one of the modifiers that caused the parse to fail so that bindings loaded at runtime can be
+ * handled gracefully.
+ */
+pub fn try_parse_key_binding(
+    pattern: String,
+    known_codes: &CodeMap,
+) -> Result<KeyCode, KeyBindingError> {
+    parse_binding(pattern, known_codes, false, &ModifierMap::default())
+}
+
+fn parse_binding(
+    pattern: String,
+    known_codes: &CodeMap,
+    strict: bool,
+    modifiers: &ModifierMap,
+) -> Result<KeyCode, KeyBindingError> {
+    if let Some(modifier) = pattern.trim().strip_prefix(TAP_PREFIX) {
+        return parse_tap_binding(modifier.trim(), known_codes, strict);
+    }
+
+    let mut parts: Vec<&str> = pattern.split('-').map(str::trim).collect();
+    let name = match parts.pop() {
+        Some(name) if !name.is_empty() => name,
+        _ => return Err(KeyBindingError::MissingKey(pattern)),
+    };
+    let code = match raw_key_code(name) {
+        Some(Some(code)) => code,
+        Some(None) => return Err(KeyBindingError::InvalidKeyCode(name.into())),
+        None => match code_for_name(name, known_codes) {
+            Some(code) => code,
+            None => return Err(unknown_key(name, known_codes)),
+        },
+    };
+
+    let mask = modifier_mask(&parts, strict, modifiers)?;
+
+    trace!(?pattern, mask, code, "parsed keybinding");
+    Ok(KeyCode::from_raw(mask, code))
+}
+
+fn unknown_key(name: &str, known_codes: &CodeMap) -> KeyBindingError {
+    match suggest_key_name(name, known_codes.keys().map(String::as_str)) {
+        Some(suggestion) => {
+            KeyBindingError::UnknownKeyWithSuggestion(name.into(), suggestion.into())
+        }
+        None => KeyBindingError::UnknownKey(name.into()),
+    }
+}
+
+// 'code:133' -> Some(Some(133)), returning None if the name is not a raw key code
+fn raw_key_code(name: &str) -> Option<Option<u8>> {
+    name.strip_prefix(KEY_CODE_PREFIX).map(parse_key_code)
+}
+
+fn parse_tap_binding(
+    modifier: &str,
+    known_codes: &CodeMap,
+    strict: bool,
+) -> Result<KeyCode, KeyBindingError> {
+    let m = parse_modifier(modifier, strict)?;
+    let name = MODIFIERS
+        .iter()
+        .find(|(k, _, _)| *k == m)
+        .map(|&(_, _, name)| name)
+        .unwrap_or_default();
+
+    match known_codes.get(name) {
+        Some(&code) => Ok(KeyCode::from_raw(0, code).as_tap()),
+        None => Err(KeyBindingError::UnknownKey(name.into())),
+    }
+}
+
+/**
+ * Convert a list of user friendly modifier names into an X modifier mask.
+ *
+ * Modifiers follow the same rules as [parse_key_binding] (short or long form, ignoring case and
+ * surrounding whitespace) making this useful for building masks for mouse or scroll bindings:
+ * `["M", "S"]` gives the mask for Super and Shift being held. An empty list gives a mask of 0.
+ */
+pub fn parse_modifiers(parts: &[&str]) -> Result<u16, KeyBindingError> {
+    modifier_mask(parts, false, &ModifierMap::default())
+}
+
+/**
+ * Convert a user friendly scroll binding into a [ScrollDirection] and modifier mask.
+ *
+ * Bindings use the same modifier syntax as [parse_key_binding] with the key name replaced by
+ * one of `ScrollUp` or `ScrollDown`: `M-ScrollUp` gives [ScrollDirection::Up] with Super held.
+ * The button to grab for the binding is `MouseButton::from(direction)`.
+ */
+pub fn parse_scroll_binding(pattern: &str) -> Result<(ScrollDirection, u16), KeyBindingError> {
+    let mut parts: Vec<&str> = pattern.split('-').map(str::trim).collect();
+    let name = parts.remove(parts.len() - 1);
+    let direction = match name {
+        "ScrollUp" => ScrollDirection::Up,
+        "ScrollDown" => ScrollDirection::Down,
+        _ => return Err(KeyBindingError::UnknownScrollDirection(name.into())),
+    };
+
+    Ok((
+        direction,
+        modifier_mask(&parts, false, &ModifierMap::default())?,
+    ))
+}
+
+fn modifier_mask(
+    parts: &[&str],
+    strict: bool,
+    modifiers: &ModifierMap,
+) -> Result<u16, KeyBindingError> {
+    parts
+        .iter()
+        .map(|s| parse_modifier(s.trim(), strict))
+        .try_fold(0, |acc, m| m.map(|m| acc | modifiers.mask(m)))
+}
+
+fn parse_modifier(s: &str, strict: bool) -> Result<ModifierKey, KeyBindingError> {
+    let res = if strict {
+        ModifierKey::try_from(s)
+    } else {
+        ModifierKey::try_from_ignoring_case(s)
+    };
+
+    res.map_err(|_| KeyBindingError::UnknownModifier(s.into()))
+}
+
+/**
+ * Convert an X keycode back into the user friendly form accepted by [parse_key_binding].
+ *
+ * Modifiers are always written in short form and in a fixed order (M, A, C, S, H) so that
+ * the output is stable: a [KeyCode] with Shift and Super held for 'j' is rendered as 'M-S-j'.
+ * Modifiers that can not be used in bindings (such as Caps Lock and Num Lock) are ignored.
+ *
+ * The key name is chosen using [name_for_code]. Tap bindings are rendered as 'tap:<modifier>'.
+ * Returns `None` if the key code is not present in `known_codes`.
+ */
+pub fn format_key_binding(key: &KeyCode, known_codes: &CodeMap) -> Option<String> {
+    if key.is_tap() {
+        return MODIFIERS
+            .iter()
+            .find(|(_, _, name)| known_codes.get(*name) == Some(&key.code()))
+            .map(|(_, m, _)| format!("{}{}", TAP_PREFIX, m));
+    }
+
+    let name = name_for_code(key.code(), known_codes)?;
+
+    Some(binding_string(key, name))
+}
+
+/**
+ * Describe an arbitrary key press received from the X server as a key binding string.
+ *
+ * `state` and `code` are the modifier state and detail of the key press event. The result is
+ * the canonical form that [format_key_binding] would give for a binding of that key press (so
+ * it can be copied directly into your bindings) with lock modifiers such as Caps Lock dropped.
+ * Key codes that are not present in `known_codes` are written using [KEY_CODE_PREFIX] which is
+ * also accepted by [parse_key_binding]: 'M-code:248'.
+ *
+ * This is intended for discovering the names of keys when writing bindings.
+ */
+pub fn describe_key_event(state: u16, code: u8, known_codes: &CodeMap) -> String {
+    let key = KeyCode::from_raw(state, code).normalised();
+
+    match name_for_code(code, known_codes) {
+        Some(name) => binding_string(&key, name),
+        None => binding_string(&key, &format!("{}{}", KEY_CODE_PREFIX, code)),
+    }
+}
+
+// The modifiers held for key in short form (in the order given by MODIFIERS) followed by name
+fn binding_string(key: &KeyCode, name: &str) -> String {
+    match modifier_string(key) {
+        mods if mods.is_empty() => name.to_string(),
+        mods => format!("{}-{}", mods, name),
+    }
+}
+
+// The modifiers held for key in short form, in the order given by MODIFIERS: 'M-S'
+fn modifier_string(key: &KeyCode) -> String {
+    let mask = key.normalised().mask();
+    MODIFIERS
+        .iter()
+        .filter(|(m, _, _)| mask & m.to_mask() > 0)
+        .map(|&(_, s, _)| s)
+        .collect::<Vec<&str>>()
+        .join("-")
+}
+
+/**
+ * Group resolved key bindings by the modifiers that they use, for rendering a cheat sheet.
+ *
+ * Each group is keyed by the modifiers of its bindings in the canonical short form used by
+ * [format_key_binding] ('M', 'M-S', 'A-C' and so on) with bindings that have no modifiers under
+ * `""` and tap bindings under [TAP_PREFIX]. Bindings keep the order they were given in within
+ * each group:
+ *
+ * ```
+ * # use penrose::core::bindings::{KeyCode, ModifierKey::*};
+ * # use penrose::xcb::helpers::group_bindings_by_mods;
+ * let bindings = vec![
+ *     (KeyCode::new(&[Meta], 44), "focus next".to_string()),
+ *     (KeyCode::new(&[Meta, Shift], 44), "move down".to_string()),
+ *     (KeyCode::new(&[Meta], 45), "focus previous".to_string()),
+ * ];
+ * let groups = group_bindings_by_mods(&bindings);
+ *
+ * assert_eq!(groups.keys().collect::<Vec<_>>(), vec!["M", "M-S"]);
+ * assert_eq!(groups["M"].len(), 2);
+ * ```
+ */
+pub fn group_bindings_by_mods(
+    bindings: &[(KeyCode, String)],
+) -> BTreeMap<String, Vec<(KeyCode, String)>> {
+    let mut groups: BTreeMap<String, Vec<(KeyCode, String)>> = BTreeMap::new();
+    for (key, description) in bindings.iter() {
+        let mods = if key.is_tap() {
+            TAP_PREFIX.to_string()
+        } else {
+            modifier_string(key)
+        };
+        groups
+            .entry(mods)
+            .or_default()
+            .push((*key, description.clone()));
+    }
+
+    groups
+}
+
+/**
+ * Look up the key name for an X keycode: the reverse of the mapping held in a [CodeMap].
+ *
+ * If more than one key name maps to the key code, names without upper case characters are
+ * preferred and ties are broken alphabetically ('a' rather than 'A', '1' rather than 'exclam')
+ * so the result does not depend on the iteration order of the map. Returns `None` if the key
+ * code is not present in `known_codes`.
+ */
+pub fn name_for_code(code: u8, known_codes: &CodeMap) -> Option<&str> {
+    known_codes
+        .iter()
+        .filter(|&(_, &c)| c == code)
+        .map(|(name, _)| name.as_str())
+        .min_by_key(|name| (name.chars().any(char::is_uppercase), *name))
+}
+
+// A single entry in the output of bindings_to_json
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct JsonBinding<'a> {
+    binding: String,
+    mask: u16,
+    code: u8,
+    description: &'a str,
+}
+
+/**
+ * Serialise resolved key bindings as a JSON array for use by external tools.
+ *
+ * Each `(KeyCode, description)` pair is written as an object containing the canonical binding
+ * string (as given by [format_key_binding]), the modifier mask and key code of the binding and
+ * the description:
+ *
+ * ```text
+ * [{"binding":"M-S-Return","mask":65,"code":36,"description":"launch a terminal"}]
+ * ```
+ *
+ * Key codes that are not present in `known_codes` are written using [KEY_CODE_PREFIX] in the
+ * same way as [describe_key_event]. The order of `bindings` is preserved.
+ */
+#[cfg(feature = "serde")]
+pub fn bindings_to_json(bindings: &[(KeyCode, String)], known_codes: &CodeMap) -> String {
+    let entries: Vec<JsonBinding<'_>> = bindings
+        .iter()
+        .map(|(key, description)| JsonBinding {
+            binding: format_key_binding(key, known_codes)
+                .unwrap_or_else(|| describe_key_event(key.mask(), key.code(), known_codes)),
+            mask: key.mask(),
+            code: key.code(),
+            description,
+        })
+        .collect();
+
+    serde_json::to_string(&entries).expect("key bindings are always serialisable")
+}
+
+/**
+ * Convert a user friendly key chord into a sequence of X keycodes.
+ *
+ * Chords are whitespace separated key bindings that need to be pressed one after another, with
+ * each key binding following the rules described for [parse_key_binding]: 'M-x g', 'M-a S-Return'.
+ */
+pub fn parse_key_chord(
+    pattern: String,
+    known_codes: &CodeMap,
+) -> Result<KeyChord, KeyBindingError> {
+    let codes = pattern
+        .split_whitespace()
+        .map(|b| try_parse_key_binding(b.into(), known_codes))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if codes.is_empty() {
+        return Err(KeyBindingError::EmptyChord);
+    }
+
+    Ok(KeyChord(codes))
+}
+
+/// Errors that can occur when loading key bindings from a TOML file
+#[cfg(feature = "toml")]
+#[derive(thiserror::Error, Debug)]
+pub enum TomlBindingsError {
+    /// The bindings file could not be read
+    #[error("unable to read bindings file: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The bindings file is not a valid TOML table of strings
+    #[error("invalid bindings file: {0}")]
+    Toml(#[from] toml::de::Error),
+
+    /// The key codes for the current keyboard layout could not be determined
+    #[error(transparent)]
+    Xmodmap(#[from] XmodmapError),
+
+    /// One or more of the key bindings in the file could not be parsed
+    #[error("invalid key bindings: {0:?}")]
+    InvalidBindings(Vec<(String, KeyBindingError)>),
+}
+
+/**
+ * Load key bindings from a TOML file of `"<binding>" = "<command>"` entries.
+ *
+ * Each binding is parsed using [try_parse_key_binding] against the current keymap (read using
+ * [try_keycodes_from_keymap][1]), allowing bindings to be changed without recompiling penrose:
+ *
+ * ```toml
+ * "M-Return" = "alacritty"
+ * "M-S-f" = "firefox"
+ * ```
+ *
+ * Bindings are returned in alphabetical order. If any of the bindings are invalid then every
+ * invalid binding is returned as part of [TomlBindingsError::InvalidBindings] rather than
+ * stopping at the first one.
+ *
+ * [1]: crate::core::helpers::try_keycodes_from_keymap
+ */
+#[cfg(feature = "toml")]
+pub fn load_bindings_from_toml(path: &Path) -> Result<Vec<(KeyCode, String)>, TomlBindingsError> {
+    let contents = std::fs::read_to_string(path)?;
+    bindings_from_toml(&contents, &try_keycodes_from_keymap()?)
+}
+
+#[cfg(feature = "toml")]
+fn bindings_from_toml(
+    contents: &str,
+    known_codes: &CodeMap,
+) -> Result<Vec<(KeyCode, String)>, TomlBindingsError> {
+    let raw: BTreeMap<String, String> = toml::from_str(contents)?;
+    let mut bindings = vec![];
+    let mut errors = vec![];
+
+    for (binding, command) in raw.into_iter() {
+        match try_parse_key_binding(binding.clone(), known_codes) {
+            Ok(code) => bindings.push((code, command)),
+            Err(e) => errors.push((binding, e)),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(bindings)
+    } else {
+        Err(TomlBindingsError::InvalidBindings(errors))
+    }
+}
+
+/**
+ * Query the X server for the modifiers that are currently held.
+ *
+ * The returned mask uses the same bit layout as the masks produced by [parse_key_binding] with
+ * lock modifiers (Caps Lock, Num Lock) and mouse button state removed, so it can be compared
+ * directly against the mask of a bound [KeyCode].
+ */
+pub fn current_modifier_mask(conn: &xcb::Connection) -> crate::xcb::Result<u16> {
+    let root = match conn.get_setup().roots().next() {
+        Some(screen) => screen.root(),
+        None => return Err(crate::xcb::XcbError::NoScreens),
+    };
+    let reply = xcb::query_pointer(conn, root).get_reply()?;
+
+    Ok(reply.mask() & BINDING_MODIFIER_MASK)
+}
+
+/**
+ * Grab a single [KeyCode] on the given root window so that it is reported to penrose.
+ *
+ * The key is grabbed with each combination of the lock modifiers (Caps Lock, Num Lock) in the
+ * same way as the bindings grabbed on startup, so it fires regardless of their state (unless
+ * `key` explicitly includes a lock modifier: see [KeyCode::uses_lock_modifier]). This allows
+ * bindings to be added at runtime (for example while in a "command mode") without needing to
+ * re-grab everything. See [ungrab_key] for removing the grab again.
+ */
+pub fn grab_key(conn: &xcb::Connection, root: Xid, key: &KeyCode) -> crate::xcb::Result<()> {
+    let mode = xcb::GRAB_MODE_ASYNC as u8;
+
+    for m in key.lock_mask_combinations() {
+        // xcb docs: https://www.mankier.com/3/xcb_grab_key
+        xcb::grab_key_checked(
+            conn,                        // xcb connection to X11
+            false,                       // don't pass grabbed events through to the client
+            root,                        // the window to grab: in this case the root window
+            key.normalised().mask() | m, // modifiers to grab
+            key.code(),                  // keycode to grab
+            mode,                        // don't lock pointer input while grabbing
+            mode,                        // don't lock keyboard input while grabbing
+        )
+        .request_check()?;
+    }
+
+    record_grab(root, key);
+    Ok(())
+}
+
+/**
+ * Drop the grab for a single [KeyCode] that was registered using [grab_key] (or grabbed as part
+ * of the startup bindings), including each of its lock modifier combinations.
+ */
+pub fn ungrab_key(conn: &xcb::Connection, root: Xid, key: &KeyCode) -> crate::xcb::Result<()> {
+    for m in key.lock_mask_combinations() {
+        // xcb docs: https://www.mankier.com/3/xcb_ungrab_key
+        xcb::ungrab_key_checked(conn, key.code(), root, key.normalised().mask() | m)
+            .request_check()?;
+    }
+
+    forget_grab(root, key);
+    Ok(())
+}
+
+/// Errors that can occur when re-grabbing key bindings using [rebind]
+#[derive(thiserror::Error, Debug)]
+pub enum RebindError {
+    /// The key codes for the new keyboard layout could not be determined
+    #[error(transparent)]
+    Xmodmap(#[from] XmodmapError),
+
+    /// One or more of the key bindings could not be parsed using the new keyboard layout
+    #[error("invalid key bindings: {0:?}")]
+    InvalidBindings(Vec<(String, KeyBindingError)>),
+
+    /// Ungrabbing the old keys or grabbing the new ones failed
+    #[error(transparent)]
+    Xcb(#[from] crate::xcb::XcbError),
+}
+
+/**
+ * Replace the keys grabbed on the given root window after the keyboard layout has changed (for
+ * example after running `setxkbmap` or plugging in a different keyboard).
+ *
+ * The key codes for the new layout are read using [reload_keycode_cache][2] (so the cache used
+ * by [keycodes_from_xmodmap_cached][1] holds the new layout) and each of `patterns` is parsed using
+ * [try_parse_key_binding]. If every pattern is valid then the keys in `old` are ungrabbed and
+ * the new keys are grabbed in their place, otherwise every invalid pattern is returned as part
+ * of [RebindError::InvalidBindings] and the existing grabs are left in place.
+ *
+ * The new keys are returned in the same order as `patterns` so that they can be paired back up
+ * with their actions.
+ *
+ * [1]: crate::core::helpers::keycodes_from_xmodmap_cached
+ * [2]: crate::core::helpers::reload_keycode_cache
+ */
+pub fn rebind(
+    conn: &xcb::Connection,
+    root: Xid,
+    old: &[KeyCode],
+    patterns: &[String],
+) -> Result<Vec<KeyCode>, RebindError> {
+    let keys = parse_all_bindings(patterns, &reload_keycode_cache()?)?;
+
+    for key in old.iter() {
+        ungrab_key(conn, root, key)?;
+    }
+    for key in keys.iter() {
+        grab_key(conn, root, key)?;
+    }
+
+    Ok(keys)
+}
+
+// Parse every pattern before any grabs are changed so that an invalid binding does not leave
+// the user without any bindings at all
+fn parse_all_bindings(
+    patterns: &[String],
+    known_codes: &CodeMap,
+) -> Result<Vec<KeyCode>, RebindError> {
+    let mut keys = vec![];
+    let mut errors = vec![];
+
+    for pattern in patterns.iter() {
+        match try_parse_key_binding(pattern.clone(), known_codes) {
+            Ok(key) => keys.push(key),
+            Err(e) => errors.push((pattern.clone(), e)),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(keys)
+    } else {
+        Err(RebindError::InvalidBindings(errors))
+    }
+}
+
+impl KeyCode {
+    /**
+     * Build a [KeyCode] from a raw X keysym value (such as those reported by `xev`) using the
+     * keyboard mapping of the X server that `conn` is connected to.
+     *
+     * As with [KeyCode::with_mask], any bits of `mask` that can not be bound are dropped.
+     * Returns `None` if the keyboard mapping can not be read or if the keysym is not bound to any
+     * physical key in the current layout.
+     */
+    pub fn from_keysym(conn: &xcb::Connection, keysym: u32, mask: u16) -> Option<KeyCode> {
+        let setup = conn.get_setup();
+        let (min, max) = (setup.min_keycode(), setup.max_keycode());
+        let mapping = xcb::get_keyboard_mapping(conn, min, max - min + 1)
+            .get_reply()
+            .ok()?;
+
+        let code = code_for_keysym(
+            mapping.keysyms(),
+            mapping.keysyms_per_keycode() as usize,
+            min,
+            keysym,
+        )?;
+
+        Some(KeyCode::new(&[], code).with_mask(mask))
+    }
+}
+
+// The keyboard mapping returned by the X server is a flat list of keysyms with a fixed number per
+// keycode, starting from 'min_code'.
+fn code_for_keysym(keysyms: &[u32], per_code: usize, min_code: u8, keysym: u32) -> Option<u8> {
+    if per_code == 0 {
+        return None;
+    }
+
+    keysyms
+        .chunks(per_code)
+        .position(|syms| syms.contains(&keysym))
+        .map(|ix| min_code + ix as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::bindings::{MouseButton, CAPS_LOCK_MASK, NUM_LOCK_MASK};
+    use strum::IntoEnumIterator;
+
+    fn codes() -> CodeMap {
+        map! {
+            "j".to_string() => 44,
+            "Return".to_string() => 36,
+        }
+    }
+
+    #[test]
+    fn valid_bindings_parse() {
+        let k = try_parse_key_binding("M-S-j".into(), &codes()).unwrap();
+        assert_eq!(k.code(), 44);
+        assert_eq!(k.mask(), (xcb::MOD_MASK_4 | xcb::MOD_MASK_SHIFT) as u16);
+    }
+
+    #[test]
+    fn misspelled_key_names_suggest_a_known_key() {
+        let res = try_parse_key_binding("M-Retrun".into(), &codes());
+        assert_eq!(
+            res,
+            Err(KeyBindingError::UnknownKeyWithSuggestion(
+                "Retrun".into(),
+                "Return".into()
+            ))
+        );
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "'Retrun' is not a known key: did you mean 'Return'?"
+        );
+    }
+
+    #[test]
+    fn bindings_without_modifiers_parse() {
+        let k = try_parse_key_binding("Return".into(), &codes()).unwrap();
+        assert_eq!(k, KeyCode::from_raw(0, 36));
+    }
+
+    #[test]
+    fn key_aliases_are_expanded() {
+        let codes = map! { "KP_Enter".to_string() => 104, };
+        let k = try_parse_key_binding("M-KPEnter".into(), &codes).unwrap();
+
+        assert_eq!(
+            k,
+            try_parse_key_binding("M-KP_Enter".into(), &codes).unwrap()
+        );
+        assert_eq!(k.code(), 104);
+    }
+
+    test_cases! {
+        key_names_ignoring_case;
+        args: (binding: &str, expected: &str);
+
+        case: space_alias => ("M-Space", "M-space");
+        case: enter_alias => ("M-Enter", "M-Return");
+        case: escape_alias => ("Esc", "Escape");
+        case: upper_case_alias => ("M-ESC", "M-Escape");
+        case: upper_case_name => ("M-SPACE", "M-space");
+        case: lower_case_name => ("M-return", "M-Return");
+        body: {
+            let codes = map! {
+                "space".to_string() => 65,
+                "Return".to_string() => 36,
+                "Escape".to_string() => 9,
+            };
+
+            assert_eq!(
+                try_parse_key_binding(binding.into(), &codes),
+                try_parse_key_binding(expected.into(), &codes)
+            );
+        }
+    }
+
+    #[test]
+    fn exact_key_names_are_preferred_over_case_insensitive_matches() {
+        let codes = map! {
+            "a".to_string() => 38,
+            "A".to_string() => 138,
+        };
+
+        assert_eq!(
+            try_parse_key_binding("a".into(), &codes).unwrap().code(),
+            38
+        );
+        assert_eq!(
+            try_parse_key_binding("A".into(), &codes).unwrap().code(),
+            138
+        );
+    }
+
+    #[test]
+    fn ambiguous_case_insensitive_key_names_are_rejected() {
+        let codes = map! {
+            "ae".to_string() => 38,
+            "AE".to_string() => 138,
+        };
+
+        assert!(try_parse_key_binding("M-Ae".into(), &codes).is_err());
+    }
+
+    #[test]
+    fn tap_bindings_parse_and_format() {
+        let codes = map! { "Super_L".to_string() => 133, };
+        let k = try_parse_key_binding("tap:M".into(), &codes).unwrap();
+
+        assert_eq!(k, KeyCode::from_raw(0, 133).as_tap());
+        assert_eq!(format_key_binding(&k, &codes), Some("tap:M".to_string()));
+    }
+
+    #[test]
+    fn tap_bindings_for_unknown_modifiers_are_an_error() {
+        let res = try_parse_key_binding("tap:Q".into(), &codes());
+        assert_eq!(res, Err(KeyBindingError::UnknownModifier("Q".into())));
+    }
+
+    test_cases! {
+        raw_key_codes;
+        args: (pattern: &str, expected: Result<KeyCode, KeyBindingError>);
+
+        case: bare_code => ("code:191", Ok(KeyCode::from_raw(0, 191)));
+        case: with_modifiers => ("M-S-code:44", Ok(KeyCode::from_raw((xcb::MOD_MASK_4 | xcb::MOD_MASK_SHIFT) as u16, 44)));
+        case: hex_code => ("M-code:0x85", Ok(KeyCode::from_raw(xcb::MOD_MASK_4 as u16, 133)));
+        case: upper_case_hex_code => ("code:0XFF", Ok(KeyCode::from_raw(0, 255)));
+        case: octal_code => ("M-code:0o205", Ok(KeyCode::from_raw(xcb::MOD_MASK_4 as u16, 133)));
+        case: out_of_range => ("M-code:256", Err(KeyBindingError::InvalidKeyCode("code:256".into())));
+        case: hex_out_of_range => ("M-code:0x100", Err(KeyBindingError::InvalidKeyCode("code:0x100".into())));
+        case: octal_out_of_range => ("M-code:0o400", Err(KeyBindingError::InvalidKeyCode("code:0o400".into())));
+        case: invalid_octal_digit => ("M-code:0o8", Err(KeyBindingError::InvalidKeyCode("code:0o8".into())));
+        case: missing_hex_digits => ("M-code:0x", Err(KeyBindingError::InvalidKeyCode("code:0x".into())));
+        case: explicit_sign => ("M-code:+5", Err(KeyBindingError::InvalidKeyCode("code:+5".into())));
+        case: not_a_number => ("M-code:j", Err(KeyBindingError::InvalidKeyCode("code:j".into())));
+        case: missing_code => ("code:", Err(KeyBindingError::InvalidKeyCode("code:".into())));
+        body: {
+            assert_eq!(try_parse_key_binding(pattern.into(), &CodeMap::new()), expected);
+        }
+    }
+
+    #[test]
+    fn whitespace_around_tokens_is_ignored() {
+        let expected = try_parse_key_binding("M-S-j".into(), &codes()).unwrap();
+        for pattern in &["M- S -j", " M-S-j ", "M -S- j"] {
+            assert_eq!(
+                try_parse_key_binding(pattern.to_string(), &codes()),
+                Ok(expected)
+            );
+        }
+    }
+
+    test_cases! {
+        parse_modifiers;
+        args: (parts: &[&str], expected: Result<u16, KeyBindingError>);
+
+        case: empty => (&[], Ok(0));
+        case: single => (&["M"], Ok(xcb::MOD_MASK_4 as u16));
+        case: multiple => (&["M", "S"], Ok((xcb::MOD_MASK_4 | xcb::MOD_MASK_SHIFT) as u16));
+        case: long_form => (&["Ctrl", " Alt "], Ok((xcb::MOD_MASK_CONTROL | xcb::MOD_MASK_1) as u16));
+        case: unknown => (&["M", "Q"], Err(KeyBindingError::UnknownModifier("Q".into())));
+        case: lower_case => (&["m", "s"], Ok((xcb::MOD_MASK_4 | xcb::MOD_MASK_SHIFT) as u16));
+        case: mixed_case => (&["cTRL", "ALT"], Ok((xcb::MOD_MASK_CONTROL | xcb::MOD_MASK_1) as u16));
+        body: {
+            assert_eq!(parse_modifiers(parts), expected);
+        }
+    }
+
+    test_cases! {
+        parse_scroll_binding;
+        args: (pattern: &str, expected: Result<(ScrollDirection, u16), KeyBindingError>);
+
+        case: up => ("ScrollUp", Ok((ScrollDirection::Up, 0)));
+        case: down => ("ScrollDown", Ok((ScrollDirection::Down, 0)));
+        case: modifiers => ("M-S-ScrollUp", Ok((ScrollDirection::Up, (xcb::MOD_MASK_4 | xcb::MOD_MASK_SHIFT) as u16)));
+        case: modifier_case_is_ignored => ("ctrl-ScrollDown", Ok((ScrollDirection::Down, xcb::MOD_MASK_CONTROL as u16)));
+        case: button_number => ("M-Button4", Err(KeyBindingError::UnknownScrollDirection("Button4".into())));
+        case: unknown_modifier => ("Q-ScrollUp", Err(KeyBindingError::UnknownModifier("Q".into())));
+        body: {
+            assert_eq!(parse_scroll_binding(pattern), expected);
+        }
+    }
+
+    #[test]
+    fn scroll_directions_match_the_x_scroll_buttons() {
+        assert_eq!(u8::from(MouseButton::from(ScrollDirection::Up)), 4);
+        assert_eq!(u8::from(MouseButton::from(ScrollDirection::Down)), 5);
+        assert_eq!(ScrollDirection::Up.button_mask(), xcb::BUTTON_MASK_4 as u16);
+        assert_eq!(
+            ScrollDirection::Down.button_mask(),
+            xcb::BUTTON_MASK_5 as u16
+        );
+    }
+
+    #[test]
+    fn modifier_case_is_ignored_unless_strict() {
+        let expected = try_parse_key_binding("M-S-j".into(), &codes()).unwrap();
+
+        for binding in &["m-s-j", "M-s-j", "super-SHIFT-j", "Super-Shift-j"] {
+            assert_eq!(
+                parse_key_binding(binding.to_string(), &codes()),
+                Some(expected)
+            );
+        }
+        assert_eq!(parse_key_binding_strict("m-s-j".into(), &codes()), None);
+        assert_eq!(
+            parse_key_binding_strict("M-S-j".into(), &codes()),
+            Some(expected)
+        );
+    }
+
+    #[test]
+    fn annotated_xmodmap_key_names_round_trip() {
+        let codes = crate::core::helpers::parse_xmodmap_output(
+            "keycode  48 = adiaeresis (ä) Adiaeresis (Ä) adiaeresis Adiaeresis",
+        );
+        let k = try_parse_key_binding("M-adiaeresis".into(), &codes).unwrap();
+
+        assert_eq!(k.code(), 48);
+        assert_eq!(
+            format_key_binding(&k, &codes),
+            Some("M-adiaeresis".to_string())
+        );
+    }
+
+    #[test]
+    fn batch_parsing_pairs_patterns_with_results() {
+        let patterns = vec![
+            "M-j".to_string(),
+            "M-notarealkey".to_string(),
+            "Return".to_string(),
+        ];
+        let parsed = parse_key_bindings(&patterns, &codes());
+        let expected = vec![
+            ("M-j".to_string(), parse_key_binding("M-j".into(), &codes())),
+            ("M-notarealkey".to_string(), None),
+            ("Return".to_string(), Some(KeyCode::from_raw(0, 36))),
+        ];
+
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn unknown_keys_are_reported() {
+        assert_eq!(
+            try_parse_key_binding("M-notarealkey".into(), &codes()),
+            Err(KeyBindingError::UnknownKey("notarealkey".into()))
+        );
+    }
+
+    test_cases! {
+        bindings_without_a_key_name;
+        args: (binding: &str);
+
+        case: empty => ("");
+        case: whitespace => ("  ");
+        case: separator_only => ("-");
+        case: trailing_separator => ("M-");
+        case: modifiers_only => ("M-S- ");
+        body: {
+            assert_eq!(
+                try_parse_key_binding(binding.into(), &codes()),
+                Err(KeyBindingError::MissingKey(binding.into()))
+            );
+            assert_eq!(parse_key_binding(binding.into(), &codes()), None);
+        }
+    }
+
+    #[test]
+    fn the_default_modifier_map_matches_the_xcb_masks() {
+        let modifiers = ModifierMap::default();
+
+        for m in ModifierKey::iter() {
+            assert_eq!(modifiers.mask(m), m.to_mask(), "{:?}", m);
+        }
+    }
+
+    test_cases! {
+        parse_key_binding_with_modifiers;
+        args: (binding: &str, expected_mask: u16);
+
+        case: remapped_modifier => ("M-j", 1 << 5);
+        case: unchanged_modifier => ("A-j", 1 << 3);
+        case: combined_modifiers => ("M-S-j", 1 << 5 | 1);
+        case: long_form_modifier => ("super-j", 1 << 5);
+        body: {
+            let modifiers = ModifierMap { meta: 1 << 5, ..Default::default() };
+            let k = parse_key_binding_with_modifiers(binding.into(), &codes(), &modifiers).unwrap();
+
+            assert_eq!(k, KeyCode::from_raw(expected_mask, 44));
+        }
+    }
+
+    test_cases! {
+        parse_key_binding_with_aliases;
+        args: (binding: &str, expected: Option<KeyCode>);
+
+        case: alias => ("super_shift-j", Some(KeyCode::from_raw(1 << 6 | 1, 44)));
+        case: alias_and_modifier => ("C-super_shift-j", Some(KeyCode::from_raw(1 << 6 | 1 << 2 | 1, 44)));
+        case: no_alias => ("M-j", Some(KeyCode::from_raw(1 << 6, 44)));
+        case: unknown_alias => ("super_ctrl-j", None);
+        body: {
+            let aliases = [("super_shift", "M-S")];
+            assert_eq!(parse_key_binding_with_aliases(binding.into(), &codes(), &aliases), expected);
+        }
+    }
+
+    #[test]
+    fn unknown_modifiers_are_reported() {
+        assert_eq!(
+            try_parse_key_binding("Q-j".into(), &codes()),
+            Err(KeyBindingError::UnknownModifier("Q".into()))
+        );
+        assert_eq!(parse_key_binding("Q-j".into(), &codes()), None);
+    }
+
+    test_cases! {
+        format_key_binding;
+        args: (binding: &str, expected: &str);
+
+        case: bare_key => ("Return", "Return");
+        case: single_modifier => ("M-j", "M-j");
+        case: modifiers_are_ordered => ("S-C-A-M-j", "M-A-C-S-j");
+        case: long_form_modifiers => ("Shift-Super-Return", "M-S-Return");
+        case: hyper => ("H-S-j", "S-H-j");
+        body: {
+            let k = try_parse_key_binding(binding.into(), &codes()).unwrap();
+            assert_eq!(format_key_binding(&k, &codes()), Some(expected.to_string()));
+        }
+    }
+
+    #[test]
+    fn formatting_ignores_lock_masks() {
+        let k = try_parse_key_binding("M-j".into(), &codes()).unwrap();
+        let k = KeyCode::from_raw(k.mask() | CAPS_LOCK_MASK | NUM_LOCK_MASK, k.code());
+        assert_eq!(format_key_binding(&k, &codes()), Some("M-j".to_string()));
+    }
+
+    test_cases! {
+        describe_key_event;
+        args: (state: u16, code: u8, expected: &str);
+
+        case: bare_key => (0, 36, "Return");
+        case: single_modifier => (1 << 6, 44, "M-j");
+        case: modifiers_are_ordered => (1 | 1 << 2 | 1 << 3 | 1 << 6, 44, "M-A-C-S-j");
+        case: lock_masks_are_ignored => (1 << 6 | CAPS_LOCK_MASK | NUM_LOCK_MASK, 44, "M-j");
+        case: mouse_buttons_are_ignored => (1 << 6 | 1 << 8, 44, "M-j");
+        case: unknown_code => (1 << 6, 248, "M-code:248");
+        case: shifted_return => (1 << 6 | 1, 36, "M-S-Return");
+        body: {
+            assert_eq!(describe_key_event(state, code, &codes()), expected);
+        }
+    }
+
+    #[test]
+    fn described_key_events_can_be_parsed() {
+        for &(state, code) in &[(1 << 6, 44), (1 | 1 << 3, 36), (1 << 2, 248)] {
+            let binding = describe_key_event(state, code, &codes());
+            assert_eq!(
+                try_parse_key_binding(binding, &codes()),
+                Ok(KeyCode::from_raw(state, code))
+            );
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn bindings_are_serialised_as_json() {
+        let bindings = vec![
+            (
+                try_parse_key_binding("M-S-Return".into(), &codes()).unwrap(),
+                "launch a terminal".to_string(),
+            ),
+            (KeyCode::from_raw(1 << 6, 248), "say \"hi\"".to_string()),
+        ];
+
+        let json: serde_json::Value =
+            serde_json::from_str(&bindings_to_json(&bindings, &codes())).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"binding": "M-S-Return", "mask": 65, "code": 36, "description": "launch a terminal"},
+                {"binding": "M-code:248", "mask": 64, "code": 248, "description": "say \"hi\""},
+            ])
+        );
+    }
+
+    #[test]
+    fn rebinding_parses_every_pattern_and_reports_all_invalid_ones() {
+        let patterns = |raw: &[&str]| raw.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        let keys = parse_all_bindings(&patterns(&["M-j", "M-S-Return"]), &codes()).unwrap();
+        assert_eq!(
+            keys,
+            vec![
+                KeyCode::from_raw(1 << 6, 44),
+                KeyCode::from_raw(1 << 6 | 1, 36)
+            ]
+        );
+
+        match parse_all_bindings(&patterns(&["M-j", "M-NotAKey", "Q-j"]), &codes()) {
+            Err(RebindError::InvalidBindings(errors)) => {
+                let invalid: Vec<&str> = errors.iter().map(|(p, _)| p.as_str()).collect();
+                assert_eq!(invalid, vec!["M-NotAKey", "Q-j"]);
+            }
+            other => panic!("expected invalid bindings, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bindings_are_grouped_by_their_canonical_modifiers() {
+        let binding = |mask, code, desc: &str| (KeyCode::from_raw(mask, code), desc.to_string());
+        let bindings = vec![
+            binding(1 << 6 | 1, 44, "swap down"),
+            binding(1 << 6, 44, "focus down"),
+            binding(0, 36, "bare return"),
+            binding(1 | 1 << 6 | CAPS_LOCK_MASK, 36, "swap main"),
+            binding(1 << 6, 36, "terminal"),
+            (KeyCode::from_raw(0, 133).as_tap(), "launcher".to_string()),
+        ];
+
+        let groups = group_bindings_by_mods(&bindings);
+        let summary: Vec<(&str, Vec<&str>)> = groups
+            .iter()
+            .map(|(mods, bs)| (mods.as_str(), bs.iter().map(|(_, d)| d.as_str()).collect()))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                ("", vec!["bare return"]),
+                ("M", vec!["focus down", "terminal"]),
+                ("M-S", vec!["swap down", "swap main"]),
+                ("tap:", vec!["launcher"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn formatting_prefers_lower_case_key_names() {
+        let codes = map! {
+            "A".to_string() => 38,
+            "a".to_string() => 38,
+            "exclam".to_string() => 10,
+            "1".to_string() => 10,
+        };
+        let k = |code| KeyCode::from_raw(0, code);
+
+        assert_eq!(format_key_binding(&k(38), &codes), Some("a".to_string()));
+        assert_eq!(format_key_binding(&k(10), &codes), Some("1".to_string()));
+        assert_eq!(format_key_binding(&k(99), &codes), None);
+    }
+
+    #[test]
+    fn conditional_bindings_skip_invalid_bindings_in_applicable_groups() {
+        let bindings = vec![
+            ("M-j".to_string(), 1),
+            ("M-notarealkey".to_string(), 2),
+            ("Q-Return".to_string(), 3),
+            ("S-Return".to_string(), 4),
+        ];
+        let loaded = load_bindings_if(&codes(), &["j", "Return"], bindings);
+
+        assert_eq!(
+            loaded.iter().map(|(_, n)| *n).collect::<Vec<_>>(),
+            vec![1, 4]
+        );
+    }
+
+    #[test]
+    fn names_are_looked_up_from_codes() {
+        let codes = map! {
+            "Return".to_string() => 36,
+            "KP_Enter".to_string() => 104,
+            "Hyper_L".to_string() => 104,
+            "j".to_string() => 44,
+            "J".to_string() => 44,
+        };
+
+        assert_eq!(name_for_code(36, &codes), Some("Return"));
+        assert_eq!(name_for_code(44, &codes), Some("j"));
+        assert_eq!(name_for_code(104, &codes), Some("Hyper_L"));
+        assert_eq!(name_for_code(99, &codes), None);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_bindings_parse() {
+        let bindings = bindings_from_toml(
+            "\"M-j\" = \"rofi -show run\"\n\"S-Return\" = \"st\"",
+            &codes(),
+        )
+        .unwrap();
+        let expected = vec![
+            (
+                try_parse_key_binding("M-j".into(), &codes()).unwrap(),
+                "rofi -show run".into(),
+            ),
+            (
+                try_parse_key_binding("S-Return".into(), &codes()).unwrap(),
+                "st".into(),
+            ),
+        ];
+
+        assert_eq!(bindings, expected);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn invalid_toml_bindings_are_all_reported() {
+        let res = bindings_from_toml(
+            "\"M-j\" = \"st\"\n\"M-notarealkey\" = \"st\"\n\"Q-j\" = \"st\"",
+            &codes(),
+        );
+
+        match res {
+            Err(TomlBindingsError::InvalidBindings(errs)) => assert_eq!(
+                errs,
+                vec![
+                    (
+                        "M-notarealkey".to_string(),
+                        KeyBindingError::UnknownKey("notarealkey".into())
+                    ),
+                    (
+                        "Q-j".to_string(),
+                        KeyBindingError::UnknownModifier("Q".into())
+                    ),
+                ]
+            ),
+            res => panic!("expected InvalidBindings, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn keysyms_are_found_in_the_keyboard_mapping() {
+        // keycodes 8, 9, 10 with two keysyms each
+        let keysyms = [0, 0, 0xff1b, 0, 0x0031, 0x0021];
+
+        assert_eq!(code_for_keysym(&keysyms, 2, 8, 0xff1b), Some(9));
+        assert_eq!(code_for_keysym(&keysyms, 2, 8, 0x0021), Some(10));
+        assert_eq!(code_for_keysym(&keysyms, 2, 8, 0xff0d), None);
+        assert_eq!(code_for_keysym(&keysyms, 0, 8, 0xff1b), None);
+    }
+
+    #[test]
+    fn key_chords_parse() {
+        let chord = parse_key_chord("M-j  S-Return".into(), &codes()).unwrap();
+        let expected = [
+            KeyCode::from_raw(xcb::MOD_MASK_4 as u16, 44),
+            KeyCode::from_raw(xcb::MOD_MASK_SHIFT as u16, 36),
+        ];
+
+        assert_eq!(chord.codes(), &expected[..]);
+        assert!(chord.starts_with(&expected[..1]));
+    }
+
+    #[test]
+    fn invalid_key_chords_are_reported() {
+        assert_eq!(
+            parse_key_chord("M-j notarealkey".into(), &codes()),
+            Err(KeyBindingError::UnknownKey("notarealkey".into()))
+        );
+        assert_eq!(
+            parse_key_chord("  ".into(), &codes()),
+            Err(KeyBindingError::EmptyChord)
+        );
     }
 }
@@ -0,0 +1,74 @@
+// Check that gen_action_bindings expands the same binding syntax at runtime that it accepts at
+// compile time. Key names are resolved using a stub xmodmap placed on the PATH so this needs to
+// run in its own test binary.
+#![cfg(feature = "xcb")]
+#[macro_use]
+extern crate penrose;
+
+use penrose::core::bindings::{KeyCode, ModifierKey};
+
+use std::{env, fs, os::unix::fs::PermissionsExt};
+
+const XMODMAP_OUTPUT: &str = "\
+keycode  36 = Return NoSymbol Return
+keycode  44 = j J j J
+keycode 104 = KP_Enter NoSymbol KP_Enter";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Grow,
+    Launch,
+}
+
+// Put an 'xmodmap' on the PATH that prints XMODMAP_OUTPUT
+fn stub_xmodmap() {
+    let dir = env::temp_dir().join(format!("penrose-stub-xmodmap-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("xmodmap");
+    fs::write(
+        &script,
+        format!("#!/bin/sh\ncat <<'EOF'\n{}\nEOF\n", XMODMAP_OUTPUT),
+    )
+    .unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let path = env::var("PATH").unwrap_or_default();
+    env::set_var("PATH", format!("{}:{}", dir.display(), path));
+    env::remove_var("PENROSE_KEYMAP_SOURCE");
+}
+
+#[test]
+fn modifier_groups_are_expanded_into_one_binding_each() {
+    use ModifierKey::*;
+    stub_xmodmap();
+
+    let bindings: Vec<(KeyCode, Action)> = gen_action_bindings! {
+        "M-[S,C]-j" => Action::Grow;
+        "#M-A-j" => Action::Grow;
+    };
+
+    assert_eq!(
+        bindings,
+        vec![
+            (KeyCode::new(&[Meta, Shift], 44), Action::Grow),
+            (KeyCode::new(&[Meta, Ctrl], 44), Action::Grow),
+        ]
+    );
+}
+
+#[test]
+fn key_groups_are_expanded_into_one_binding_each() {
+    stub_xmodmap();
+
+    let bindings: Vec<(KeyCode, Action)> = gen_action_bindings! {
+        "M-(Return|KP_Enter)" => Action::Launch;
+    };
+
+    assert_eq!(
+        bindings,
+        vec![
+            (KeyCode::new(&[ModifierKey::Meta], 36), Action::Launch),
+            (KeyCode::new(&[ModifierKey::Meta], 104), Action::Launch),
+        ]
+    );
+}
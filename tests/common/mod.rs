@@ -16,14 +16,14 @@ use std::collections::HashMap;
 
 const SCREEN_WIDTH: u32 = 1000;
 const SCREEN_HEIGHT: u32 = 600;
-pub const EXIT_CODE: KeyCode = KeyCode { mask: 0, code: 0 };
-pub const LAYOUT_CHANGE_CODE: KeyCode = KeyCode { mask: 0, code: 1 };
-pub const WORKSPACE_CHANGE_CODE: KeyCode = KeyCode { mask: 0, code: 2 };
-pub const SCREEN_CHANGE_CODE: KeyCode = KeyCode { mask: 0, code: 3 };
-pub const FOCUS_CHANGE_CODE: KeyCode = KeyCode { mask: 0, code: 4 };
-pub const KILL_CLIENT_CODE: KeyCode = KeyCode { mask: 0, code: 5 };
-pub const ADD_WORKSPACE_CODE: KeyCode = KeyCode { mask: 0, code: 6 };
-pub const CLIENT_TO_WORKSPACE_CODE: KeyCode = KeyCode { mask: 0, code: 7 };
+pub const EXIT_CODE: KeyCode = KeyCode::new(&[], 0);
+pub const LAYOUT_CHANGE_CODE: KeyCode = KeyCode::new(&[], 1);
+pub const WORKSPACE_CHANGE_CODE: KeyCode = KeyCode::new(&[], 2);
+pub const SCREEN_CHANGE_CODE: KeyCode = KeyCode::new(&[], 3);
+pub const FOCUS_CHANGE_CODE: KeyCode = KeyCode::new(&[], 4);
+pub const KILL_CLIENT_CODE: KeyCode = KeyCode::new(&[], 5);
+pub const ADD_WORKSPACE_CODE: KeyCode = KeyCode::new(&[], 6);
+pub const CLIENT_TO_WORKSPACE_CODE: KeyCode = KeyCode::new(&[], 7);
 
 pub fn simple_screen(n: usize) -> Screen {
     Screen::new(
@@ -0,0 +1,24 @@
+// Check that the closure registered with set_panic_cleanup is run when penrose panics. This
+// installs a global panic hook so it needs to run in its own test binary to avoid the hook
+// firing for panics in unrelated tests.
+use penrose::core::helpers::set_panic_cleanup;
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+#[test]
+fn panic_cleanup_is_run_on_panic() {
+    let ran = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&ran);
+    set_panic_cleanup(Box::new(move || flag.store(true, Ordering::SeqCst)));
+
+    let res = thread::spawn(|| panic!("expected panic")).join();
+
+    assert!(res.is_err());
+    assert!(ran.load(Ordering::SeqCst));
+}
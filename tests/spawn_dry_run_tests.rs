@@ -0,0 +1,26 @@
+// Check that commands are logged rather than run while spawn dry runs are enabled. The dry run
+// flag is global so this needs to run in its own test binary to avoid interfering with the
+// spawn tests in the main library.
+use penrose::core::helpers::{set_spawn_dry_run, spawn, spawn_dry_run, spawn_pid};
+
+use std::{env, fs, thread, time::Duration};
+
+#[test]
+fn commands_are_not_run_during_a_dry_run() {
+    let marker = env::temp_dir().join(format!("penrose-dry-run-{}", std::process::id()));
+    let _ = fs::remove_file(&marker);
+
+    set_spawn_dry_run(true);
+    assert!(spawn_dry_run());
+
+    // Spawning a missing program is an error if it is actually run
+    assert!(spawn("penrose-not-a-real-program --new-window").is_ok());
+    assert_eq!(spawn_pid("penrose-not-a-real-program --new-window"), None);
+    assert_eq!(spawn_pid(format!("touch {}", marker.display())), None);
+
+    set_spawn_dry_run(false);
+    assert!(!spawn_dry_run());
+
+    thread::sleep(Duration::from_millis(100));
+    assert!(!marker.exists());
+}